@@ -3,10 +3,18 @@
 # Installation:
 #   Copy this file to ~/.config/fish/functions/pj.fish
 #   Or run: cp pj.fish ~/.config/fish/functions/
+#
+# Usage:
+#   pj [pattern]       Jump to a matching project
+#   pj -- <pattern>    Jump even if the pattern is also a subcommand name,
+#                      e.g. `pj -- session` for a project called session
 
 function pj --description "Project Jump - jump to project directories"
-    # If --help or --version or --init-config is passed, just run the binary
-    if contains -- $argv[1] --help --version -h -V --init-config
+    # Lets `pj doctor` know the wrapper is installed
+    set -lx PJ_WRAPPER 1
+
+    # If --help, --version, --init-config or a subcommand that doesn't jump is passed, just run the binary
+    if contains -- $argv[1] --help --version -h -V --init-config config daemon doctor export --scan-stats foreach import mcp open serve session tag trust watch which
         command pj $argv
         return
     end
@@ -21,7 +29,19 @@ function pj --description "Project Jump - jump to project directories"
         if test "$result" != "$PWD"
             command pj --set-prev "$PWD" 2>/dev/null
         end
+
+        # Run on_leave hooks of the project we jumped into last time
+        if set -q _pj_project; and test "$_pj_project" != "$result"
+            command pj hooks --leave $_pj_project | source
+        end
+
         cd $result
+
+        # Run on_enter hooks of the new project
+        if test "$_pj_project" != "$result"
+            set -g _pj_project $result
+            command pj hooks --enter $result | source
+        end
     else if test $exit_code -eq 0; and test -n "$result"
         # If result is not empty but not a directory, print it
         echo $result
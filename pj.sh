@@ -4,19 +4,23 @@
 # Installation:
 #   Add to ~/.bashrc or ~/.zshrc:
 #     source /path/to/pj.sh
+#
+# Usage:
+#   pj [pattern]       Jump to a matching project
+#   pj -- <pattern>    Jump even if the pattern is also a subcommand name,
+#                      e.g. `pj -- session` for a project called session
 
 pj() {
-    # If --help or --version is passed, just run the binary directly
-    if [[ "$1" == "--help" ]] || [[ "$1" == "--version" ]] || [[ "$1" == "-h" ]] || [[ "$1" == "-V" ]]; then
-        command pj "$@"
-        return
-    fi
+    # Lets `pj doctor` know the wrapper is installed
+    local -x PJ_WRAPPER=1
 
-    # If --init-config is passed, run and return
-    if [[ "$1" == "--init-config" ]]; then
-        command pj "$@"
-        return
-    fi
+    # If --help, --version, --init-config or a subcommand that doesn't jump is passed, just run the binary
+    case "$1" in
+        --help|--version|-h|-V|--init-config|--scan-stats|config|daemon|doctor|export|foreach|import|mcp|open|serve|session|tag|trust|watch|which)
+            command pj "$@"
+            return
+            ;;
+    esac
 
     # Run pj and capture output
     local result
@@ -29,7 +33,19 @@ pj() {
         if [[ "$result" != "$PWD" ]]; then
             command pj --set-prev "$PWD" 2>/dev/null
         fi
+
+        # Run on_leave hooks of the project we jumped into last time
+        if [[ -n "$_PJ_PROJECT" ]] && [[ "$_PJ_PROJECT" != "$result" ]]; then
+            eval "$(command pj hooks --leave "$_PJ_PROJECT")"
+        fi
+
         cd "$result" || return 1
+
+        # Run on_enter hooks of the new project
+        if [[ "$_PJ_PROJECT" != "$result" ]]; then
+            _PJ_PROJECT=$result
+            eval "$(command pj hooks --enter "$result")"
+        fi
     elif [[ $exit_code -eq 0 ]] && [[ -n "$result" ]]; then
         # If result is not empty but not a directory, print it
         echo "$result"
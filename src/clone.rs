@@ -0,0 +1,215 @@
+use crate::config::Config;
+use anyhow::{Context, Result, bail};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Host directory used for repositories cloned from a local path
+const LOCAL_HOST: &str = "local";
+
+/// Version control tool used by `pj clone`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Vcs {
+    #[default]
+    Git,
+    Jj,
+}
+
+/// Split a repository URL into a host and path segments. Handles
+/// `scheme://[user@]host[:port]/path`, scp-style `[user@]host:path`,
+/// `file://` URLs and plain local paths.
+fn parse_url(url: &str) -> (String, Vec<String>) {
+    let url = url.trim().trim_end_matches('/');
+
+    let (host, path) = if let Some(path) = url.strip_prefix("file://") {
+        (None, path)
+    } else if let Some((_, rest)) = url.split_once("://") {
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let host = authority.rsplit('@').next().unwrap_or(authority);
+        let host = host.split(':').next().unwrap_or(host);
+        (Some(host), path)
+    } else if let Some((authority, path)) = url.split_once(':')
+        && !authority.contains('/')
+        && !url.starts_with('.')
+    {
+        let host = authority.rsplit('@').next().unwrap_or(authority);
+        (Some(host), path)
+    } else {
+        (None, url)
+    };
+
+    let mut segments: Vec<String> = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    if let Some(last) = segments.last_mut()
+        && let Some(stripped) = last.strip_suffix(".git")
+        && !stripped.is_empty()
+    {
+        *last = stripped.to_string();
+    }
+
+    match host {
+        Some(host) => (host.to_string(), segments),
+        None => {
+            // Keep only `parent/repo` of a local path, e.g. /srv/git/team/api.git -> team/api
+            let keep = segments.len().saturating_sub(2);
+            (LOCAL_HOST.to_string(), segments.split_off(keep))
+        }
+    }
+}
+
+/// Where a repository is cloned to, relative to the clone root: `host/org/repo`
+pub fn relative_destination(url: &str) -> Result<PathBuf> {
+    let (host, segments) = parse_url(url);
+
+    if host.is_empty() || segments.is_empty() {
+        bail!("Could not derive a destination from URL: {}", url);
+    }
+
+    let relative: PathBuf = std::iter::once(host).chain(segments).collect();
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        bail!("Refusing to clone to an unsafe path derived from: {}", url);
+    }
+
+    Ok(relative)
+}
+
+/// Run the clone command, sending its output to stderr so that stdout only
+/// carries the destination path for the shell wrapper
+fn run_clone(vcs: Vcs, url: &str, dest: &Path) -> Result<()> {
+    let mut command = match vcs {
+        Vcs::Git => {
+            let mut command = Command::new("git");
+            command.arg("clone").arg("--").arg(url).arg(dest);
+            command
+        }
+        Vcs::Jj => {
+            let mut command = Command::new("jj");
+            command.args(["git", "clone"]).arg("--").arg(url).arg(dest);
+            command
+        }
+    };
+
+    let status = command
+        .stdout(Stdio::from(std::io::stderr()))
+        .status()
+        .with_context(|| format!("Failed to run {:?}", command.get_program()))?;
+
+    if !status.success() {
+        bail!("Clone of {} failed ({})", url, status);
+    }
+
+    Ok(())
+}
+
+/// Clone `url` under the configured clone root and return the new project path.
+/// If the destination already exists as a project it is returned unchanged.
+pub fn clone(config: &Config, url: &str, vcs: Vcs) -> Result<PathBuf> {
+    let root = config
+        .clone_root()
+        .context("No clone_root or scan_paths configured")?;
+    let dest = root.join(relative_destination(url)?);
+
+    if dest.exists() {
+        let is_project = config
+            .project_markers
+            .iter()
+            .any(|marker| dest.join(marker).exists());
+        if is_project {
+            return Ok(dest);
+        }
+        bail!("Destination already exists: {}", dest.display());
+    }
+
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    run_clone(vcs, url, &dest)?;
+    Ok(dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_destination_from_https_url() {
+        assert_eq!(
+            relative_destination("https://github.com/jcowgar/pj.git").unwrap(),
+            PathBuf::from("github.com/jcowgar/pj")
+        );
+        assert_eq!(
+            relative_destination("https://user@gitlab.example.com:8443/group/sub/repo/").unwrap(),
+            PathBuf::from("gitlab.example.com/group/sub/repo")
+        );
+    }
+
+    #[test]
+    fn test_destination_from_scp_and_ssh_urls() {
+        assert_eq!(
+            relative_destination("git@github.com:jcowgar/pj.git").unwrap(),
+            PathBuf::from("github.com/jcowgar/pj")
+        );
+        assert_eq!(
+            relative_destination("ssh://git@github.com/jcowgar/pj").unwrap(),
+            PathBuf::from("github.com/jcowgar/pj")
+        );
+    }
+
+    #[test]
+    fn test_destination_from_local_sources() {
+        assert_eq!(
+            relative_destination("file:///srv/git/team/api.git").unwrap(),
+            PathBuf::from("local/team/api")
+        );
+        assert_eq!(
+            relative_destination("/srv/git/team/api.git").unwrap(),
+            PathBuf::from("local/team/api")
+        );
+    }
+
+    #[test]
+    fn test_destination_rejects_unsafe_paths() {
+        assert!(relative_destination("https://github.com/../etc").is_err());
+        assert!(relative_destination("https://github.com").is_err());
+    }
+
+    #[test]
+    fn test_clone_local_bare_repo() {
+        if Command::new("git").arg("--version").output().is_err() {
+            return;
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("remote/team/api.git");
+        let status = Command::new("git")
+            .args(["init", "--quiet", "--bare"])
+            .arg(&source)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let root = temp_dir.path().join("projects");
+        let config = Config {
+            scan_paths: vec![root.clone().into()],
+            ..Config::default()
+        };
+
+        let url = format!("file://{}", source.display());
+        let dest = clone(&config, &url, Vcs::Git).unwrap();
+
+        assert_eq!(dest, root.join("local/team/api"));
+        assert!(dest.join(".git").is_dir());
+
+        // Cloning again returns the existing project
+        assert_eq!(clone(&config, &url, Vcs::Git).unwrap(), dest);
+    }
+}
@@ -1,22 +1,136 @@
+use crate::hooks::Hooks;
+use crate::matcher::RankingConfig;
+use crate::session::SessionConfig;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// System-wide config file, applied before the user's config
+const SYSTEM_CONFIG_PATH: &str = "/etc/pj/config.toml";
+
+/// A directory to scan for projects, with optional per-path settings. In
+/// TOML this is either a plain path string or a table such as
+/// `{ path = "~/Work", follow_symlinks = true }`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(from = "ScanPathRepr", into = "ScanPathRepr")]
+pub struct ScanPath {
+    pub path: PathBuf,
+    /// Overrides the global `follow_symlinks` for this scan path
+    pub follow_symlinks: Option<bool>,
+}
+
+impl ScanPath {
+    /// The path with `~` expanded
+    pub fn expanded(&self) -> PathBuf {
+        PathBuf::from(shellexpand::tilde(&self.path.to_string_lossy()).to_string())
+    }
+}
+
+impl From<PathBuf> for ScanPath {
+    fn from(path: PathBuf) -> Self {
+        Self {
+            path,
+            follow_symlinks: None,
+        }
+    }
+}
+
+impl From<&Path> for ScanPath {
+    fn from(path: &Path) -> Self {
+        path.to_path_buf().into()
+    }
+}
+
+impl From<&str> for ScanPath {
+    fn from(path: &str) -> Self {
+        PathBuf::from(path).into()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ScanPathRepr {
+    Path(PathBuf),
+    Table {
+        path: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        follow_symlinks: Option<bool>,
+    },
+}
+
+impl From<ScanPathRepr> for ScanPath {
+    fn from(repr: ScanPathRepr) -> Self {
+        match repr {
+            ScanPathRepr::Path(path) => path.into(),
+            ScanPathRepr::Table {
+                path,
+                follow_symlinks,
+            } => Self {
+                path,
+                follow_symlinks,
+            },
+        }
+    }
+}
+
+impl From<ScanPath> for ScanPathRepr {
+    fn from(scan_path: ScanPath) -> Self {
+        match scan_path.follow_symlinks {
+            None => ScanPathRepr::Path(scan_path.path),
+            follow_symlinks => ScanPathRepr::Table {
+                path: scan_path.path,
+                follow_symlinks,
+            },
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Config {
     /// Directories to scan for projects
-    pub scan_paths: Vec<PathBuf>,
+    pub scan_paths: Vec<ScanPath>,
 
     /// Project markers to look for (in order of preference)
     pub project_markers: Vec<String>,
 
     /// Maximum depth to scan
-    #[serde(default = "default_max_depth")]
     pub max_depth: usize,
-}
 
-fn default_max_depth() -> usize {
-    5
+    /// Glob patterns for directories the scan skips, e.g. `node_modules`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+
+    /// Treat scan diagnostics (unreadable directories, missing scan paths) as errors
+    pub strict: bool,
+
+    /// Descend into symlinked directories (can be overridden per scan path)
+    pub follow_symlinks: bool,
+
+    /// Where `pj clone` puts repositories (defaults to the first scan path)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clone_root: Option<PathBuf>,
+
+    /// Shell command run in a project created by `pj new`, e.g. `git init`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_create: Option<String>,
+
+    /// Enter/leave hooks keyed by a glob matched against project paths
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub hooks: BTreeMap<String, Hooks>,
+
+    /// How `pj session` creates and attaches to multiplexer sessions
+    pub session: SessionConfig,
+
+    /// Editor command for `pj open`, keyed by project kind (e.g. `rust = "nvim"`)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub editors: BTreeMap<String, String>,
+
+    /// Boosts and penalties used to order fuzzy matches
+    pub ranking: RankingConfig,
 }
 
 impl Default for Config {
@@ -25,7 +139,8 @@ impl Default for Config {
             scan_paths: vec![
                 dirs::home_dir()
                     .unwrap_or_else(|| PathBuf::from("."))
-                    .join("Projects"),
+                    .join("Projects")
+                    .into(),
             ],
             project_markers: vec![
                 ".git".to_string(),
@@ -34,32 +149,468 @@ impl Default for Config {
                 ".project".to_string(),
             ],
             max_depth: 5,
+            exclude: Vec::new(),
+            strict: false,
+            follow_symlinks: false,
+            clone_root: None,
+            post_create: None,
+            hooks: BTreeMap::new(),
+            session: SessionConfig::default(),
+            editors: BTreeMap::new(),
+            ranking: RankingConfig::default(),
+        }
+    }
+}
+
+/// Where an effective config value came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// Built-in default
+    Default,
+    /// A config file (system, user or included)
+    File(PathBuf),
+    /// An environment variable
+    Env(&'static str),
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File(path) => write!(f, "{}", path.display()),
+            ConfigSource::Env(var) => write!(f, "${}", var),
+        }
+    }
+}
+
+/// The source of each field of an effective [`Config`]
+#[derive(Debug, Clone)]
+pub struct ConfigOrigins {
+    pub scan_paths: ConfigSource,
+    pub project_markers: ConfigSource,
+    pub max_depth: ConfigSource,
+    pub exclude: ConfigSource,
+    pub strict: ConfigSource,
+    pub follow_symlinks: ConfigSource,
+    pub clone_root: ConfigSource,
+    pub post_create: ConfigSource,
+    /// Per pattern, since hooks from every layer are merged
+    pub hooks: BTreeMap<String, ConfigSource>,
+    pub session: ConfigSource,
+    /// Per project kind, since editors from every layer are merged
+    pub editors: BTreeMap<String, ConfigSource>,
+    pub ranking: ConfigSource,
+}
+
+impl Default for ConfigOrigins {
+    fn default() -> Self {
+        Self {
+            scan_paths: ConfigSource::Default,
+            project_markers: ConfigSource::Default,
+            max_depth: ConfigSource::Default,
+            exclude: ConfigSource::Default,
+            strict: ConfigSource::Default,
+            follow_symlinks: ConfigSource::Default,
+            clone_root: ConfigSource::Default,
+            post_create: ConfigSource::Default,
+            hooks: BTreeMap::new(),
+            session: ConfigSource::Default,
+            editors: BTreeMap::new(),
+            ranking: ConfigSource::Default,
+        }
+    }
+}
+
+/// A fully merged config along with where each value came from
+#[derive(Debug, Clone, Default)]
+pub struct LoadedConfig {
+    pub config: Config,
+    pub origins: ConfigOrigins,
+    /// Config files that were applied, in order
+    pub files: Vec<PathBuf>,
+    /// Keys in those files that pj doesn't recognise and ignored
+    pub unknown_keys: Vec<UnknownKey>,
+}
+
+/// An error loading the layered configuration
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    /// A config file could not be read
+    #[error("Failed to read config file: {}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    /// A config file is not valid TOML or has a value of the wrong type
+    #[error(transparent)]
+    Parse(#[from] ConfigParseError),
+    /// A config file includes itself, directly or through other files
+    #[error("Config include cycle detected at: {}", .0.display())]
+    IncludeCycle(PathBuf),
+    /// An environment variable has a value pj can't use
+    #[error("Invalid {var} value: {value}")]
+    InvalidEnv { var: &'static str, value: String },
+    /// There is no config directory to look for the user config in
+    #[error("Could not determine config directory")]
+    NoConfigDir,
+}
+
+/// A config file that failed to parse, with the location of the problem
+#[derive(Debug)]
+pub struct ConfigParseError {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+/// The 1-based line and column of byte `offset` in `contents`
+fn line_column(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset.min(contents.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+impl ConfigParseError {
+    fn new(path: &Path, contents: &str, err: &toml::de::Error) -> Self {
+        let offset = err.span().map_or(0, |span| span.start);
+        let (line, column) = line_column(contents, offset);
+
+        Self {
+            path: path.to_path_buf(),
+            line,
+            column,
+            message: err.message().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.path.display(),
+            self.line,
+            self.column,
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for ConfigParseError {}
+
+/// A config key that pj doesn't recognise, such as a misspelled option. It
+/// is ignored when loading; `pj config check` reports it as an error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownKey {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    /// Dotted path of the key, e.g. `ranking.exact_basenme`
+    pub key: String,
+}
+
+impl fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: unknown config key `{}`",
+            self.path.display(),
+            self.line,
+            self.column,
+            self.key
+        )
+    }
+}
+
+/// Find the keys of `contents` that did not survive deserializing into a
+/// [`ConfigLayer`]: whatever isn't in the layer serialized back is unknown.
+fn unknown_keys(path: &Path, contents: &str, layer: &ConfigLayer) -> Vec<UnknownKey> {
+    let (Ok(document), Ok(toml::Value::Table(known))) = (
+        toml::de::DeTable::parse(contents),
+        toml::Value::try_from(layer),
+    ) else {
+        return Vec::new();
+    };
+
+    let mut unknown = Vec::new();
+    collect_unknown_keys(document.get_ref(), &known, "", &mut |key, offset| {
+        let (line, column) = line_column(contents, offset);
+        unknown.push(UnknownKey {
+            path: path.to_path_buf(),
+            line,
+            column,
+            key,
+        });
+    });
+    unknown.sort_by_key(|key| (key.line, key.column));
+    unknown
+}
+
+fn collect_unknown_keys(
+    table: &toml::de::DeTable<'_>,
+    known: &toml::Table,
+    prefix: &str,
+    report: &mut impl FnMut(String, usize),
+) {
+    use toml::de::DeValue;
+
+    for (key, value) in table {
+        let name = format!("{}{}", prefix, key.get_ref());
+        let Some(known_value) = known.get(key.get_ref().as_ref()) else {
+            // Fields skipped when empty, such as `session.windows = []`,
+            // don't round-trip but aren't unknown either
+            let empty = match value.get_ref() {
+                DeValue::Array(array) => array.is_empty(),
+                DeValue::Table(table) => table.is_empty(),
+                _ => false,
+            };
+            if !empty {
+                report(name, key.span().start);
+            }
+            continue;
+        };
+
+        match (value.get_ref(), known_value) {
+            (DeValue::Table(table), toml::Value::Table(known)) => {
+                collect_unknown_keys(table, known, &format!("{}.", name), report);
+            }
+            (DeValue::Array(array), toml::Value::Array(known)) => {
+                for (i, (item, known)) in array.iter().zip(known).enumerate() {
+                    if let (DeValue::Table(table), toml::Value::Table(known)) =
+                        (item.get_ref(), known)
+                    {
+                        let prefix = format!("{}[{}].", name, i);
+                        collect_unknown_keys(table, known, &prefix, report);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A single config file (or environment) layer. Every field is optional so
+/// that a layer only overrides what it actually sets.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ConfigLayer {
+    scan_paths: Option<Vec<ScanPath>>,
+    project_markers: Option<Vec<String>>,
+    max_depth: Option<usize>,
+    exclude: Option<Vec<String>>,
+    strict: Option<bool>,
+    follow_symlinks: Option<bool>,
+    clone_root: Option<PathBuf>,
+    post_create: Option<String>,
+
+    /// Merged into, rather than replacing, hooks from earlier layers
+    hooks: Option<BTreeMap<String, Hooks>>,
+    session: Option<SessionConfig>,
+
+    /// Merged into, rather than replacing, editors from earlier layers
+    editors: Option<BTreeMap<String, String>>,
+    ranking: Option<RankingConfig>,
+
+    /// Other config files to apply before this one
+    #[serde(default)]
+    include: Vec<PathBuf>,
+}
+
+impl ConfigLayer {
+    /// Build a layer from the `PJ_SCAN_PATHS` and `PJ_MAX_DEPTH` variables
+    fn from_env(var: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let scan_paths = var("PJ_SCAN_PATHS")
+            .filter(|s| !s.is_empty())
+            .map(|s| std::env::split_paths(&s).map(ScanPath::from).collect());
+
+        let max_depth = match var("PJ_MAX_DEPTH") {
+            Some(s) => Some(s.trim().parse().map_err(|_| ConfigError::InvalidEnv {
+                var: "PJ_MAX_DEPTH",
+                value: s,
+            })?),
+            None => None,
+        };
+
+        Ok(Self {
+            scan_paths,
+            max_depth,
+            ..Self::default()
+        })
+    }
+
+    fn apply_to(self, loaded: &mut LoadedConfig, source: &ConfigSource) {
+        if let Some(scan_paths) = self.scan_paths {
+            loaded.config.scan_paths = scan_paths;
+            loaded.origins.scan_paths = source.clone();
+        }
+        if let Some(project_markers) = self.project_markers {
+            loaded.config.project_markers = project_markers;
+            loaded.origins.project_markers = source.clone();
+        }
+        if let Some(max_depth) = self.max_depth {
+            loaded.config.max_depth = max_depth;
+            loaded.origins.max_depth = source.clone();
+        }
+        if let Some(exclude) = self.exclude {
+            loaded.config.exclude = exclude;
+            loaded.origins.exclude = source.clone();
+        }
+        if let Some(strict) = self.strict {
+            loaded.config.strict = strict;
+            loaded.origins.strict = source.clone();
+        }
+        if let Some(follow_symlinks) = self.follow_symlinks {
+            loaded.config.follow_symlinks = follow_symlinks;
+            loaded.origins.follow_symlinks = source.clone();
+        }
+        if let Some(clone_root) = self.clone_root {
+            loaded.config.clone_root = Some(clone_root);
+            loaded.origins.clone_root = source.clone();
+        }
+        if let Some(post_create) = self.post_create {
+            loaded.config.post_create = Some(post_create);
+            loaded.origins.post_create = source.clone();
+        }
+        for (pattern, hooks) in self.hooks.unwrap_or_default() {
+            loaded.origins.hooks.insert(pattern.clone(), source.clone());
+            loaded.config.hooks.insert(pattern, hooks);
+        }
+        if let Some(session) = self.session {
+            loaded.config.session = session;
+            loaded.origins.session = source.clone();
+        }
+        for (kind, editor) in self.editors.unwrap_or_default() {
+            loaded.origins.editors.insert(kind.clone(), source.clone());
+            loaded.config.editors.insert(kind, editor);
+        }
+        if let Some(ranking) = self.ranking {
+            loaded.config.ranking = ranking;
+            loaded.origins.ranking = source.clone();
         }
     }
 }
 
 impl Config {
-    /// Load config from ~/.config/pj/config.toml or use defaults
-    pub fn load() -> Result<Self> {
-        let config_path = Self::config_path()?;
+    /// Load the layered configuration without a `--config` override
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::load_from(None)
+    }
+
+    /// Load the layered configuration (see [`Config::load_report`])
+    pub fn load_from(explicit: Option<&Path>) -> Result<Self, ConfigError> {
+        Ok(Self::load_report(explicit)?.config)
+    }
+
+    /// Load the layered configuration. Later layers override earlier ones:
+    ///
+    /// 1. Built-in defaults
+    /// 2. `/etc/pj/config.toml`
+    /// 3. The user config: `explicit` (from `--config`), else `$PJ_CONFIG`,
+    ///    else `~/.config/pj/config.toml`
+    /// 4. `PJ_SCAN_PATHS` and `PJ_MAX_DEPTH` environment variables
+    ///
+    /// Files listed in a layer's `include` are applied just before that layer.
+    /// There is no project-local layer; per-project settings live in the
+    /// project's [`ProjectFile`](crate::project_file::ProjectFile).
+    pub fn load_report(explicit: Option<&Path>) -> Result<LoadedConfig, ConfigError> {
+        let mut loaded = LoadedConfig::default();
+
+        let system_path = Path::new(SYSTEM_CONFIG_PATH);
+        if system_path.exists() {
+            apply_file(&mut loaded, system_path, &mut Vec::new())?;
+        }
 
-        if config_path.exists() {
-            let contents =
-                std::fs::read_to_string(&config_path).context("Failed to read config file")?;
-            let config: Config =
-                toml::from_str(&contents).context("Failed to parse config file")?;
-            Ok(config)
-        } else {
-            Ok(Self::default())
+        match Self::user_config_path(explicit)? {
+            (path, true) => apply_file(&mut loaded, &path, &mut Vec::new())?,
+            (path, false) if path.exists() => apply_file(&mut loaded, &path, &mut Vec::new())?,
+            _ => {}
         }
+
+        apply_env(&mut loaded, |name| std::env::var(name).ok())?;
+
+        Ok(loaded)
+    }
+
+    /// SHA-256 of the effective config, as lowercase hex. Two processes that
+    /// resolved the same files and environment get the same fingerprint.
+    pub fn fingerprint(&self) -> String {
+        let serialized = serde_json::to_vec(self).unwrap_or_default();
+        Sha256::digest(&serialized)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
     }
 
-    fn config_path() -> Result<PathBuf> {
-        let config_dir = dirs::config_dir()
-            .context("Could not determine config directory")?
-            .join("pj");
+    /// Non-fatal problems with an effective config, such as scan paths that
+    /// do not exist
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
 
-        Ok(config_dir.join("config.toml"))
+        for scan_path in &self.scan_paths {
+            let expanded = scan_path.expanded();
+            if !expanded.is_dir() {
+                warnings.push(format!("Scan path does not exist: {}", expanded.display()));
+            }
+        }
+
+        for (child, parent) in crate::scanner::overlapping_scan_paths(self) {
+            warnings.push(format!(
+                "Scan path {} is inside scan path {}; projects under it are listed once, relative to {}",
+                child.display(),
+                parent.display(),
+                child.display()
+            ));
+        }
+
+        for pattern in &self.exclude {
+            if let Err(err) = crate::scanner::Exclude::new(pattern) {
+                warnings.push(err.to_string());
+            }
+        }
+
+        for pattern in self.hooks.keys() {
+            if let Err(err) = glob::Pattern::new(&shellexpand::tilde(pattern)) {
+                warnings.push(format!("Invalid hooks pattern {:?}: {}", pattern, err));
+            }
+        }
+
+        if self.project_markers.is_empty() {
+            warnings.push("No project markers configured; nothing will be found".to_string());
+        }
+
+        if self.max_depth == 0 {
+            warnings.push("max_depth is 0; only scan paths themselves are checked".to_string());
+        }
+
+        warnings
+    }
+
+    /// Resolve the user config path, and whether it was explicitly requested
+    /// (in which case it must exist)
+    fn user_config_path(explicit: Option<&Path>) -> Result<(PathBuf, bool), ConfigError> {
+        if let Some(path) = explicit {
+            return Ok((path.to_path_buf(), true));
+        }
+
+        if let Some(path) = std::env::var_os("PJ_CONFIG").filter(|p| !p.is_empty()) {
+            return Ok((PathBuf::from(path), true));
+        }
+
+        Ok((Self::config_path()?, false))
+    }
+
+    fn config_path() -> Result<PathBuf, ConfigError> {
+        Ok(Self::config_dir()?.join("config.toml"))
+    }
+
+    /// The user's pj config directory, e.g. ~/.config/pj
+    pub fn config_dir() -> Result<PathBuf, ConfigError> {
+        Ok(dirs::config_dir()
+            .ok_or(ConfigError::NoConfigDir)?
+            .join("pj"))
     }
 
     /// Create a default config file
@@ -78,6 +629,21 @@ impl Config {
         Ok(config_path)
     }
 
+    /// The directory `pj clone` clones into, with `~` expanded
+    pub fn clone_root(&self) -> Option<PathBuf> {
+        match &self.clone_root {
+            Some(root) => Some(PathBuf::from(
+                shellexpand::tilde(&root.to_string_lossy()).to_string(),
+            )),
+            None => self.scan_paths.first().map(ScanPath::expanded),
+        }
+    }
+
+    /// Whether the scan of `scan_path` should descend into symlinked directories
+    pub fn follows_symlinks(&self, scan_path: &ScanPath) -> bool {
+        scan_path.follow_symlinks.unwrap_or(self.follow_symlinks)
+    }
+
     /// Load config from a specific TOML string (for testing)
     #[cfg(test)]
     pub fn from_toml_str(toml_str: &str) -> Result<Self> {
@@ -85,9 +651,67 @@ impl Config {
     }
 }
 
+/// Apply the environment layer, recording each variable as its own source
+fn apply_env(
+    loaded: &mut LoadedConfig,
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<(), ConfigError> {
+    let layer = ConfigLayer::from_env(var)?;
+
+    if let Some(scan_paths) = layer.scan_paths {
+        loaded.config.scan_paths = scan_paths;
+        loaded.origins.scan_paths = ConfigSource::Env("PJ_SCAN_PATHS");
+    }
+    if let Some(max_depth) = layer.max_depth {
+        loaded.config.max_depth = max_depth;
+        loaded.origins.max_depth = ConfigSource::Env("PJ_MAX_DEPTH");
+    }
+
+    Ok(())
+}
+
+/// Apply a config file and its includes on top of `loaded`. `stack` holds the
+/// files currently being applied so include cycles can be reported.
+fn apply_file(
+    loaded: &mut LoadedConfig,
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+) -> Result<(), ConfigError> {
+    let path = PathBuf::from(shellexpand::tilde(&path.to_string_lossy()).to_string());
+    let io_error = |source| ConfigError::Io {
+        path: path.clone(),
+        source,
+    };
+    let canonical = path.canonicalize().map_err(io_error)?;
+
+    if stack.contains(&canonical) {
+        return Err(ConfigError::IncludeCycle(path));
+    }
+
+    let contents = std::fs::read_to_string(&path).map_err(io_error)?;
+    let layer: ConfigLayer =
+        toml::from_str(&contents).map_err(|e| ConfigParseError::new(&path, &contents, &e))?;
+    let unknown = unknown_keys(&path, &contents, &layer);
+
+    stack.push(canonical);
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    for include in &layer.include {
+        let include = shellexpand::tilde(&include.to_string_lossy()).to_string();
+        apply_file(loaded, &base_dir.join(include), stack)?;
+    }
+    stack.pop();
+
+    layer.apply_to(loaded, &ConfigSource::File(path.clone()));
+    loaded.files.push(path);
+    loaded.unknown_keys.extend(unknown);
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
+    use tempfile::TempDir;
 
     #[test]
     fn test_default_config() {
@@ -139,12 +763,214 @@ mod tests {
     }
 
     #[test]
-    fn test_config_missing_fields_fails() {
+    fn test_config_missing_fields_use_defaults() {
         let toml_str = r#"
             max_depth = 3
         "#;
 
-        let result = Config::from_toml_str(toml_str);
-        assert!(result.is_err()); // Missing required fields
+        let config = Config::from_toml_str(toml_str).unwrap();
+        assert_eq!(config.max_depth, 3);
+        assert_eq!(config.project_markers, Config::default().project_markers);
+        assert_eq!(config.scan_paths, Config::default().scan_paths);
+    }
+
+    #[test]
+    fn test_scan_path_table_form() {
+        let toml_str = r#"
+            follow_symlinks = false
+            scan_paths = ["~/Projects", { path = "/mnt/work", follow_symlinks = true }]
+        "#;
+
+        let config = Config::from_toml_str(toml_str).unwrap();
+
+        assert_eq!(config.scan_paths[0], ScanPath::from("~/Projects"));
+        assert!(!config.follows_symlinks(&config.scan_paths[0]));
+        assert!(config.follows_symlinks(&config.scan_paths[1]));
+
+        // Plain paths round-trip as strings, tables as tables
+        let toml_out = toml::to_string(&config).unwrap();
+        assert!(toml_out.contains("\"~/Projects\""));
+        assert!(toml_out.contains("follow_symlinks = true"));
+    }
+
+    #[test]
+    fn test_layer_overrides_only_set_fields() {
+        let mut loaded = LoadedConfig::default();
+        let layer: ConfigLayer = toml::from_str("max_depth = 2").unwrap();
+        let source = ConfigSource::File(PathBuf::from("/etc/pj/config.toml"));
+        layer.apply_to(&mut loaded, &source);
+
+        assert_eq!(loaded.config.max_depth, 2);
+        assert_eq!(loaded.origins.max_depth, source);
+        assert_eq!(loaded.origins.project_markers, ConfigSource::Default);
+        assert_eq!(
+            loaded.config.project_markers,
+            Config::default().project_markers
+        );
+    }
+
+    #[test]
+    fn test_parse_error_reports_line_and_column() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.toml");
+        fs::write(&path, "max_depth = 3\nstrict = yes\n").unwrap();
+
+        let err = apply_file(&mut LoadedConfig::default(), &path, &mut Vec::new()).unwrap_err();
+        let ConfigError::Parse(err) = err else {
+            panic!("expected a parse error, got {:?}", err);
+        };
+
+        assert_eq!(err.line, 2);
+        assert_eq!(err.column, 10);
+    }
+
+    #[test]
+    fn test_unknown_keys_are_ignored_and_reported() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.toml");
+        fs::write(
+            &path,
+            concat!(
+                "max_depth = 3\n",
+                "scan_path = [\"/x\"]\n",
+                "[ranking]\n",
+                "exact_basename = 5\n",
+                "  depth_penalti = 1\n",
+                "[session]\n",
+                "windows = []\n",
+            ),
+        )
+        .unwrap();
+
+        let mut loaded = LoadedConfig::default();
+        apply_file(&mut loaded, &path, &mut Vec::new()).unwrap();
+
+        assert_eq!(loaded.config.max_depth, 3);
+        assert_eq!(loaded.config.ranking.exact_basename, 5);
+        let unknown: Vec<_> = loaded
+            .unknown_keys
+            .iter()
+            .map(|key| (key.key.as_str(), key.line, key.column))
+            .collect();
+        assert_eq!(
+            unknown,
+            vec![("scan_path", 2, 1), ("ranking.depth_penalti", 5, 3)]
+        );
+    }
+
+    #[test]
+    fn test_include_applied_before_including_file() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("team.toml"),
+            "scan_paths = [\"/team\"]\nmax_depth = 7\n",
+        )
+        .unwrap();
+        let user = temp_dir.path().join("config.toml");
+        fs::write(&user, "include = [\"team.toml\"]\nmax_depth = 3\n").unwrap();
+
+        let mut loaded = LoadedConfig::default();
+        apply_file(&mut loaded, &user, &mut Vec::new()).unwrap();
+
+        assert_eq!(loaded.config.scan_paths, vec![ScanPath::from("/team")]);
+        assert_eq!(loaded.config.max_depth, 3);
+        assert_eq!(loaded.origins.max_depth, ConfigSource::File(user.clone()));
+        assert_eq!(loaded.files.len(), 2);
+    }
+
+    #[test]
+    fn test_merged_tables_track_origin_per_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let team = temp_dir.path().join("team.toml");
+        fs::write(
+            &team,
+            "[editors]\nrust = \"vim\"\npython = \"emacs\"\n[hooks.\"~/work/*\"]\non_enter = \"ls\"\n",
+        )
+        .unwrap();
+        let user = temp_dir.path().join("config.toml");
+        fs::write(
+            &user,
+            "include = [\"team.toml\"]\n[editors]\nrust = \"code\"\n[hooks.\"~/play/*\"]\non_enter = \"pwd\"\n",
+        )
+        .unwrap();
+
+        let mut loaded = LoadedConfig::default();
+        apply_file(&mut loaded, &user, &mut Vec::new()).unwrap();
+
+        let team = ConfigSource::File(team);
+        let user = ConfigSource::File(user);
+        assert_eq!(loaded.config.editors["rust"], "code");
+        assert_eq!(loaded.origins.editors["rust"], user);
+        assert_eq!(loaded.origins.editors["python"], team);
+        assert_eq!(loaded.origins.hooks["~/work/*"], team);
+        assert_eq!(loaded.origins.hooks["~/play/*"], user);
+        assert!(loaded.unknown_keys.is_empty());
+    }
+
+    #[test]
+    fn test_include_cycle_is_an_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let a = temp_dir.path().join("a.toml");
+        fs::write(&a, "include = [\"b.toml\"]\n").unwrap();
+        fs::write(temp_dir.path().join("b.toml"), "include = [\"a.toml\"]\n").unwrap();
+
+        let result = apply_file(&mut LoadedConfig::default(), &a, &mut Vec::new());
+        assert!(matches!(result, Err(ConfigError::IncludeCycle(path)) if path == a));
+    }
+
+    #[test]
+    fn test_env_layer() {
+        let mut loaded = LoadedConfig::default();
+        apply_env(&mut loaded, |name| match name {
+            "PJ_SCAN_PATHS" => Some("/a:/b".to_string()),
+            "PJ_MAX_DEPTH" => Some("9".to_string()),
+            _ => None,
+        })
+        .unwrap();
+
+        assert_eq!(
+            loaded.config.scan_paths,
+            vec![ScanPath::from("/a"), ScanPath::from("/b")]
+        );
+        assert_eq!(loaded.config.max_depth, 9);
+        assert_eq!(loaded.origins.max_depth, ConfigSource::Env("PJ_MAX_DEPTH"));
+    }
+
+    #[test]
+    fn test_fingerprint_follows_effective_config() {
+        let mut loaded = LoadedConfig::default();
+        let default = loaded.config.fingerprint();
+        assert_eq!(default, Config::default().fingerprint());
+
+        apply_env(&mut loaded, |name| {
+            (name == "PJ_MAX_DEPTH").then(|| "2".to_string())
+        })
+        .unwrap();
+        assert_ne!(loaded.config.fingerprint(), default);
+    }
+
+    #[test]
+    fn test_env_layer_rejects_bad_depth() {
+        let result = ConfigLayer::from_env(|name| match name {
+            "PJ_MAX_DEPTH" => Some("deep".to_string()),
+            _ => None,
+        });
+        assert!(matches!(
+            result,
+            Err(ConfigError::InvalidEnv {
+                var: "PJ_MAX_DEPTH",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_missing_explicit_config_is_an_io_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let missing = temp_dir.path().join("missing.toml");
+
+        let err = Config::load_from(Some(&missing)).unwrap_err();
+        assert!(matches!(&err, ConfigError::Io { path, .. } if *path == missing));
+        assert!(std::error::Error::source(&err).is_some());
     }
 }
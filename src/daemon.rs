@@ -0,0 +1,583 @@
+use crate::config::Config;
+use crate::history::History;
+use crate::matcher::{Match, Score, rank};
+use crate::scanner::Project;
+use crate::state::state_dir;
+use crate::tags::{TagStore, split_tag_terms};
+use crate::watch::{ProjectWatcher, RESCAN_INTERVAL};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// How often the daemon applies filesystem events to its project list
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long a client waits on the daemon before giving up on it
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Socket the daemon listens on, in the pj state directory
+pub fn socket_path() -> Result<PathBuf> {
+    Ok(state_dir()?.join("daemon.sock"))
+}
+
+/// A request line sent to the daemon
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "method", rename_all = "kebab-case")]
+pub enum Request {
+    /// Projects matching `pattern`, best first. `#tag` terms filter by tag
+    /// and an empty pattern matches every project, as on the command line.
+    Query { pattern: String },
+    /// Every project
+    List,
+    /// Add a visit to `path` to the history
+    RecordVisit { path: PathBuf },
+    /// Reload the config and scan again
+    Rescan,
+    /// Nothing; the response carries the config fingerprint
+    Status,
+}
+
+/// A project in a response
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProjectEntry {
+    pub path: PathBuf,
+    pub display_path: String,
+    /// Total ranking score, for `query` responses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<i64>,
+    /// The components of `score`, as shown by `pj --explain`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub components: Option<Score>,
+    /// Whether a query term equals the project's last path segment
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exact: bool,
+}
+
+impl From<&Project> for ProjectEntry {
+    fn from(project: &Project) -> Self {
+        Self {
+            path: project.path.clone(),
+            display_path: project.display_path.clone(),
+            score: None,
+            components: None,
+            exact: false,
+        }
+    }
+}
+
+impl From<Match> for ProjectEntry {
+    fn from(m: Match) -> Self {
+        Self {
+            score: Some(m.score.total()),
+            components: Some(m.score),
+            exact: m.exact,
+            ..Self::from(&m.project)
+        }
+    }
+}
+
+impl From<ProjectEntry> for Project {
+    fn from(entry: ProjectEntry) -> Self {
+        Project {
+            path: entry.path,
+            display_path: entry.display_path,
+        }
+    }
+}
+
+impl From<ProjectEntry> for Match {
+    fn from(entry: ProjectEntry) -> Self {
+        Match {
+            score: entry.components.unwrap_or_default(),
+            exact: entry.exact,
+            indices: Vec::new(),
+            project: entry.into(),
+        }
+    }
+}
+
+/// A response line from the daemon
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Response {
+    pub ok: bool,
+    /// [`Config::fingerprint`] of the config the daemon resolved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub projects: Option<Vec<ProjectEntry>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Response {
+    fn projects(projects: Vec<ProjectEntry>) -> Self {
+        Self {
+            ok: true,
+            projects: Some(projects),
+            ..Self::default()
+        }
+    }
+
+    fn ok() -> Self {
+        Self {
+            ok: true,
+            ..Self::default()
+        }
+    }
+
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            error: Some(message.into()),
+            ..Self::default()
+        }
+    }
+}
+
+/// What the daemon keeps in memory between requests
+pub struct DaemonState {
+    config_path: Option<PathBuf>,
+    config: Config,
+    watcher: ProjectWatcher,
+    history: History,
+    /// Where the history and tags live
+    state_dir: PathBuf,
+}
+
+impl DaemonState {
+    /// Load the config and history and do the initial scan
+    pub fn load(config_path: Option<&Path>) -> Result<Self> {
+        Self::new(config_path, state_dir()?)
+    }
+
+    fn new(config_path: Option<&Path>, state_dir: PathBuf) -> Result<Self> {
+        let (config, watcher) = watch_config(config_path)?;
+
+        Ok(Self {
+            config_path: config_path.map(Path::to_path_buf),
+            config,
+            watcher,
+            history: History::load(state_dir.join("history.toml"))?,
+            state_dir,
+        })
+    }
+
+    fn projects(&self) -> &[Project] {
+        self.watcher.projects()
+    }
+
+    /// Reload the config, scan and start watching again
+    fn rescan(&mut self) -> Result<()> {
+        (self.config, self.watcher) = watch_config(self.config_path.as_deref())?;
+        Ok(())
+    }
+
+    /// Apply pending filesystem events
+    fn poll(&mut self) -> Result<()> {
+        self.watcher.poll(Duration::ZERO)?;
+        Ok(())
+    }
+
+    /// Answer a single request
+    pub fn handle(&mut self, request: Request) -> Response {
+        let mut response = self.answer(request);
+        response.config = Some(self.config.fingerprint());
+        response
+    }
+
+    fn answer(&mut self, request: Request) -> Response {
+        match request {
+            Request::Query { pattern } => match self.query(&pattern) {
+                Ok(matches) => Response::projects(matches.into_iter().map(Into::into).collect()),
+                Err(err) => Response::error(format!("{:#}", err)),
+            },
+            Request::List => {
+                Response::projects(self.projects().iter().map(ProjectEntry::from).collect())
+            }
+            Request::RecordVisit { path } => {
+                // Don't overwrite history written while the daemon was running
+                let result = self.history.reload().and_then(|()| {
+                    self.history.record(&path);
+                    self.history.save()
+                });
+                match result {
+                    Ok(()) => Response::ok(),
+                    Err(err) => Response::error(format!("{:#}", err)),
+                }
+            }
+            Request::Rescan => match self.rescan() {
+                Ok(()) => Response::ok(),
+                Err(err) => Response::error(format!("{:#}", err)),
+            },
+            Request::Status => Response::ok(),
+        }
+    }
+}
+
+impl DaemonState {
+    /// Filter by the pattern's `#tag` terms, then rank the rest
+    fn query(&mut self, pattern: &str) -> Result<Vec<Match>> {
+        let (tags, rest) = split_tag_terms(pattern);
+        let mut projects = self.projects().to_vec();
+        if !tags.is_empty() {
+            projects = TagStore::load(self.state_dir.join("tags.toml"))?.filter(projects, &tags);
+        }
+
+        // Pick up visits recorded by other processes and `pj import`
+        self.history.reload()?;
+        Ok(rank(
+            projects,
+            &rest,
+            &self.config.ranking,
+            self.history.clone(),
+        ))
+    }
+}
+
+/// Load the config, scan and start watching its scan paths
+fn watch_config(config_path: Option<&Path>) -> Result<(Config, ProjectWatcher)> {
+    let config = Config::load_from(config_path)?;
+    let watcher = ProjectWatcher::new(config.clone())?;
+    warn_if_incomplete(&watcher);
+    Ok((config, watcher))
+}
+
+fn warn_if_incomplete(watcher: &ProjectWatcher) {
+    if !watcher.is_complete() {
+        eprintln!(
+            "pj daemon: can't watch every scan path directory (inotify watch limit?); \
+             rescanning every {} seconds",
+            RESCAN_INTERVAL.as_secs()
+        );
+    }
+}
+
+/// Lock the daemon state. A thread that panicked while holding the lock
+/// leaves the state as it was between two assignments, which is still
+/// usable, so keep serving rather than take the daemon down.
+fn lock(state: &Mutex<DaemonState>) -> MutexGuard<'_, DaemonState> {
+    state.lock().unwrap_or_else(|poisoned| {
+        state.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+/// Answer a `rescan` request, scanning without holding the lock so other
+/// connections are answered meanwhile
+fn rescan(state: &Mutex<DaemonState>) -> Response {
+    let config_path = lock(state).config_path.clone();
+    match watch_config(config_path.as_deref()) {
+        Ok((config, watcher)) => {
+            let mut state = lock(state);
+            state.config = config;
+            state.watcher = watcher;
+            state.handle(Request::Status)
+        }
+        Err(err) => {
+            let mut response = Response::error(format!("{:#}", err));
+            response.config = Some(lock(state).config.fingerprint());
+            response
+        }
+    }
+}
+
+/// Apply pending filesystem events. If not every directory is watched and
+/// `rescan_due`, scan everything instead; returns whether it did. The scan
+/// runs without the lock and its result is swapped in afterwards.
+fn update(state: &Mutex<DaemonState>, rescan_due: bool) -> Result<bool> {
+    let config = {
+        let mut state = lock(state);
+        if !rescan_due || state.watcher.is_complete() {
+            state.poll()?;
+            return Ok(false);
+        }
+        state.config.clone()
+    };
+
+    let watcher = ProjectWatcher::new(config.clone())?;
+    let mut state = lock(state);
+    // A `rescan` request may have loaded another config in the meantime
+    if state.config.fingerprint() == config.fingerprint() {
+        state.watcher = watcher;
+    }
+    Ok(true)
+}
+
+/// Answer JSON-lines requests on one connection until it closes
+fn serve_connection(stream: UnixStream, state: &Mutex<DaemonState>) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str(&line) {
+            Ok(Request::Rescan) => rescan(state),
+            Ok(request) => lock(state).handle(request),
+            Err(err) => Response::error(format!("Invalid request: {}", err)),
+        };
+        serde_json::to_writer(&mut writer, &response)?;
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Accept connections on `listener` forever, one thread per connection
+pub fn serve(listener: UnixListener, state: Arc<Mutex<DaemonState>>) -> Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream.context("Failed to accept connection")?;
+        let state = Arc::clone(&state);
+        std::thread::spawn(move || {
+            if let Err(err) = serve_connection(stream, &state) {
+                eprintln!("pj daemon: {:#}", err);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Run the daemon in the foreground: listen on [`socket_path`] and keep the
+/// project list current by watching the scan paths
+pub fn run(config_path: Option<&Path>) -> Result<()> {
+    let socket = socket_path()?;
+    if Client::connect_to(&socket).is_some() {
+        bail!("pj daemon is already running on {}", socket.display());
+    }
+    // A socket nobody answers on is left over from a daemon that died
+    let _ = std::fs::remove_file(&socket);
+
+    let state = Arc::new(Mutex::new(DaemonState::load(config_path)?));
+    let listener = UnixListener::bind(&socket)
+        .with_context(|| format!("Failed to listen on {}", socket.display()))?;
+    eprintln!("pj daemon listening on {}", socket.display());
+
+    let watch_state = Arc::clone(&state);
+    std::thread::spawn(move || {
+        let mut last_scan = Instant::now();
+        loop {
+            std::thread::sleep(WATCH_INTERVAL);
+            let rescan_due = last_scan.elapsed() >= RESCAN_INTERVAL;
+            match update(&watch_state, rescan_due) {
+                Ok(true) => last_scan = Instant::now(),
+                Ok(false) => {}
+                Err(err) => eprintln!("pj daemon: {:#}", err),
+            }
+        }
+    });
+
+    serve(listener, state)
+}
+
+/// A connection to a running daemon
+pub struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Client {
+    /// Connect to the daemon, or `None` if it isn't running
+    pub fn connect() -> Option<Self> {
+        Self::connect_to(&socket_path().ok()?)
+    }
+
+    /// Connect to the daemon if it is running with the same effective
+    /// `config` as this process, so its projects are the ones a scan would
+    /// find. `None` means scan locally instead.
+    pub fn connect_for(config: &Config) -> Option<Self> {
+        let mut client = Self::connect()?;
+        let response = client.request(&Request::Status).ok()?;
+        (response.config? == config.fingerprint()).then_some(client)
+    }
+
+    /// Connect to the daemon listening on `socket`. Requests that get no
+    /// answer within a second fail, so a wedged daemon can't hang the
+    /// caller.
+    pub fn connect_to(socket: &Path) -> Option<Self> {
+        let writer = UnixStream::connect(socket).ok()?;
+        writer.set_read_timeout(Some(CLIENT_TIMEOUT)).ok()?;
+        writer.set_write_timeout(Some(CLIENT_TIMEOUT)).ok()?;
+        let reader = BufReader::new(writer.try_clone().ok()?);
+        Some(Self { reader, writer })
+    }
+
+    /// Send a request and wait for its response. A response with `ok: false`
+    /// is returned as an error.
+    pub fn request(&mut self, request: &Request) -> Result<Response> {
+        serde_json::to_writer(&mut self.writer, request)?;
+        self.writer.write_all(b"\n")?;
+
+        let mut line = String::new();
+        self.reader
+            .read_line(&mut line)
+            .context("Failed to read from pj daemon")?;
+        let response: Response =
+            serde_json::from_str(&line).context("Invalid response from pj daemon")?;
+
+        if !response.ok {
+            bail!(
+                "pj daemon: {}",
+                response.error.as_deref().unwrap_or("request failed")
+            );
+        }
+        Ok(response)
+    }
+
+    /// Projects matching `pattern`, best first, scored as by [`rank`]
+    pub fn query(&mut self, pattern: &str) -> Result<Vec<Match>> {
+        let request = Request::Query {
+            pattern: pattern.to_string(),
+        };
+        let response = self.request(&request)?;
+        Ok(response
+            .projects
+            .unwrap_or_default()
+            .into_iter()
+            .map(Match::from)
+            .collect())
+    }
+
+    /// Every project the daemon knows about
+    pub fn list(&mut self) -> Result<Vec<Project>> {
+        let response = self.request(&Request::List)?;
+        Ok(response
+            .projects
+            .unwrap_or_default()
+            .into_iter()
+            .map(Project::from)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn state_for(temp_dir: &TempDir) -> DaemonState {
+        let projects = temp_dir.path().join("projects");
+        fs::create_dir_all(projects.join("api/.git")).unwrap();
+        fs::create_dir_all(projects.join("web/.git")).unwrap();
+
+        let config_path = temp_dir.path().join("config.toml");
+        fs::write(
+            &config_path,
+            format!("scan_paths = [{:?}]\nmax_depth = 2\n", projects),
+        )
+        .unwrap();
+
+        DaemonState::new(Some(&config_path), temp_dir.path().to_path_buf()).unwrap()
+    }
+
+    #[test]
+    fn test_request_wire_format() {
+        let request: Request =
+            serde_json::from_str(r#"{"method":"query","pattern":"api"}"#).unwrap();
+        assert_eq!(
+            request,
+            Request::Query {
+                pattern: "api".to_string()
+            }
+        );
+        let request: Request =
+            serde_json::from_str(r#"{"method":"record-visit","path":"/p/api"}"#).unwrap();
+        assert_eq!(
+            request,
+            Request::RecordVisit {
+                path: PathBuf::from("/p/api")
+            }
+        );
+    }
+
+    #[test]
+    fn test_handle_requests() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = state_for(&temp_dir);
+
+        let response = state.handle(Request::List);
+        assert_eq!(response.projects.unwrap().len(), 2);
+
+        let response = state.handle(Request::Query {
+            pattern: "api".to_string(),
+        });
+        let projects = response.projects.unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].display_path, "api");
+        assert!(projects[0].score.is_some());
+        assert!(projects[0].exact);
+
+        let mut tags = TagStore::load(temp_dir.path().join("tags.toml")).unwrap();
+        tags.add(&temp_dir.path().join("projects/web"), "frontend");
+        tags.save().unwrap();
+        let response = state.handle(Request::Query {
+            pattern: "#frontend".to_string(),
+        });
+        let projects = response.projects.unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].display_path, "web");
+
+        fs::create_dir_all(temp_dir.path().join("projects/cli/.git")).unwrap();
+        assert!(state.handle(Request::Rescan).ok);
+        assert_eq!(state.handle(Request::List).projects.unwrap().len(), 3);
+
+        // Picked up by the watcher without a rescan
+        fs::create_dir_all(temp_dir.path().join("projects/docs/.git")).unwrap();
+        for _ in 0..50 {
+            state.poll().unwrap();
+            if state.projects().len() == 4 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        assert_eq!(state.projects().len(), 4);
+
+        let api = temp_dir.path().join("projects/api");
+        assert!(state.handle(Request::RecordVisit { path: api.clone() }).ok);
+        assert_eq!(state.history.get(&api).unwrap().rank, 1.0);
+
+        let status = state.handle(Request::Status);
+        assert_eq!(status.config, Some(state.config.fingerprint()));
+    }
+
+    #[test]
+    fn test_socket_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = Arc::new(Mutex::new(state_for(&temp_dir)));
+        let socket = temp_dir.path().join("daemon.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+        std::thread::spawn(move || serve(listener, state));
+
+        let mut client = Client::connect_to(&socket).unwrap();
+        assert_eq!(client.list().unwrap().len(), 2);
+
+        let response = client
+            .request(&Request::Query {
+                pattern: "web".to_string(),
+            })
+            .unwrap();
+        assert_eq!(response.projects.unwrap()[0].display_path, "web");
+
+        let matches = client.query("web").unwrap();
+        assert_eq!(matches[0].project.display_path, "web");
+        assert!(matches[0].exact);
+        assert!(matches[0].score.fuzzy > 0);
+    }
+
+    #[test]
+    fn test_client_times_out() {
+        let temp_dir = TempDir::new().unwrap();
+        let socket = temp_dir.path().join("daemon.sock");
+        // Accepts connections into the backlog but never answers
+        let _listener = UnixListener::bind(&socket).unwrap();
+
+        let mut client = Client::connect_to(&socket).unwrap();
+        let start = Instant::now();
+        assert!(client.request(&Request::Status).is_err());
+        assert!(start.elapsed() < CLIENT_TIMEOUT * 5);
+    }
+}
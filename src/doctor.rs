@@ -0,0 +1,236 @@
+use crate::config::Config;
+use crate::resolve::enclosing_scan_root;
+use crate::scanner::{Exclude, excludes};
+use std::path::{Path, PathBuf};
+
+/// Why a directory is, or is not, picked up as a project
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// The directory would be listed as a project
+    Found,
+    /// The directory does not exist
+    Missing,
+    /// The directory is not inside any configured scan path
+    NotUnderScanPath,
+    /// The directory is deeper below its scan path than `max_depth`
+    TooDeep { depth: usize, max_depth: usize },
+    /// The directory, or the directory `dir` above it, matches an `exclude`
+    /// pattern
+    Excluded { dir: PathBuf, pattern: String },
+    /// The scan reaches the directory only through a symlink, and
+    /// `follow_symlinks` is off for its scan path
+    BehindSymlink(PathBuf),
+    /// None of the configured project markers are present
+    NoMarkers,
+}
+
+/// Everything `pj doctor` knows about a single directory
+#[derive(Debug, Clone)]
+pub struct PathDiagnosis {
+    pub path: PathBuf,
+    /// The scan path containing `path`, if any
+    pub scan_root: Option<PathBuf>,
+    /// Depth of `path` below `scan_root`
+    pub depth: Option<usize>,
+    /// Configured markers present in `path`
+    pub markers: Vec<String>,
+    pub verdict: Verdict,
+}
+
+/// The first directory strictly between `root` and `path` that is a symlink.
+/// The scan does not descend into symlinks, but a symlink to a project is
+/// itself still listed, so `path` is not checked.
+fn symlink_between(root: &Path, path: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(root).ok()?.parent()?;
+    let mut current = root.to_path_buf();
+
+    for component in relative.components() {
+        current.push(component);
+        if current.symlink_metadata().is_ok_and(|m| m.is_symlink()) {
+            return Some(current);
+        }
+    }
+
+    None
+}
+
+/// The first directory below `root`, down to and including `path`, that an
+/// exclude pattern matches
+fn excluded_between(root: &Path, path: &Path, excludes: &[Exclude]) -> Option<(PathBuf, String)> {
+    let relative = path.strip_prefix(root).ok()?;
+    let mut current = root.to_path_buf();
+
+    for component in relative.components() {
+        current.push(component);
+        if let Some(exclude) = excludes.iter().find(|e| e.matches(&current)) {
+            return Some((current, exclude.pattern.clone()));
+        }
+    }
+
+    None
+}
+
+/// Explain whether `path` would be found by a scan with `config`, and if not, why
+pub fn diagnose_path(config: &Config, path: &Path) -> PathDiagnosis {
+    let markers: Vec<String> = config
+        .project_markers
+        .iter()
+        .filter(|marker| path.join(marker).exists())
+        .cloned()
+        .collect();
+
+    let enclosing = enclosing_scan_root(config, path);
+    let depth = enclosing
+        .as_ref()
+        .and_then(|(_, root)| path.strip_prefix(root).ok())
+        .map(|relative| relative.components().count());
+
+    let verdict = if !path.is_dir() {
+        Verdict::Missing
+    } else if let (Some((scan_path, root)), Some(depth)) = (&enclosing, depth) {
+        let behind_symlink = if config.follows_symlinks(scan_path) {
+            None
+        } else {
+            symlink_between(root, path)
+        };
+
+        // Invalid patterns are reported by `Config::warnings`
+        let excluded = excluded_between(root, path, &excludes(config).unwrap_or_default());
+
+        if depth > config.max_depth {
+            Verdict::TooDeep {
+                depth,
+                max_depth: config.max_depth,
+            }
+        } else if let Some((dir, pattern)) = excluded {
+            Verdict::Excluded { dir, pattern }
+        } else if let Some(link) = behind_symlink {
+            Verdict::BehindSymlink(link)
+        } else if markers.is_empty() {
+            Verdict::NoMarkers
+        } else {
+            Verdict::Found
+        }
+    } else {
+        Verdict::NotUnderScanPath
+    };
+
+    PathDiagnosis {
+        path: path.to_path_buf(),
+        scan_root: enclosing.map(|(_, root)| root),
+        depth,
+        markers,
+        verdict,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn config_for(root: &Path, max_depth: usize) -> Config {
+        Config {
+            scan_paths: vec![root.into()],
+            project_markers: vec![".git".to_string(), ".jj".to_string()],
+            max_depth,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_diagnose_found_project() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path().join("ai/app");
+        fs::create_dir_all(project.join(".git")).unwrap();
+
+        let diagnosis = diagnose_path(&config_for(temp_dir.path(), 3), &project);
+
+        assert_eq!(diagnosis.verdict, Verdict::Found);
+        assert_eq!(diagnosis.depth, Some(2));
+        assert_eq!(diagnosis.markers, vec![".git".to_string()]);
+    }
+
+    #[test]
+    fn test_diagnose_too_deep() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path().join("a/b/c");
+        fs::create_dir_all(project.join(".git")).unwrap();
+
+        let diagnosis = diagnose_path(&config_for(temp_dir.path(), 2), &project);
+
+        assert_eq!(
+            diagnosis.verdict,
+            Verdict::TooDeep {
+                depth: 3,
+                max_depth: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_diagnose_excluded() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path().join("web/node_modules/dep");
+        fs::create_dir_all(project.join(".git")).unwrap();
+
+        let config = Config {
+            exclude: vec!["node_modules".to_string()],
+            ..config_for(temp_dir.path(), 5)
+        };
+        let diagnosis = diagnose_path(&config, &project);
+
+        assert_eq!(
+            diagnosis.verdict,
+            Verdict::Excluded {
+                dir: temp_dir.path().join("web/node_modules"),
+                pattern: "node_modules".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_diagnose_no_markers_and_outside_scan_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let scan_root = temp_dir.path().join("projects");
+        let plain = scan_root.join("plain");
+        let outside = temp_dir.path().join("elsewhere");
+        fs::create_dir_all(&plain).unwrap();
+        fs::create_dir_all(outside.join(".git")).unwrap();
+
+        let config = config_for(&scan_root, 3);
+
+        assert_eq!(diagnose_path(&config, &plain).verdict, Verdict::NoMarkers);
+        assert_eq!(
+            diagnose_path(&config, &outside).verdict,
+            Verdict::NotUnderScanPath
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_diagnose_behind_symlink() {
+        let temp_dir = TempDir::new().unwrap();
+        let scan_root = temp_dir.path().join("projects");
+        let target = temp_dir.path().join("data/client");
+        fs::create_dir_all(target.join("app/.git")).unwrap();
+        fs::create_dir_all(&scan_root).unwrap();
+        std::os::unix::fs::symlink(&target, scan_root.join("client")).unwrap();
+
+        let config = config_for(&scan_root, 3);
+        let diagnosis = diagnose_path(&config, &scan_root.join("client/app"));
+
+        assert_eq!(
+            diagnosis.verdict,
+            Verdict::BehindSymlink(scan_root.join("client"))
+        );
+
+        let config = Config {
+            follow_symlinks: true,
+            ..config
+        };
+        let diagnosis = diagnose_path(&config, &scan_root.join("client/app"));
+        assert_eq!(diagnosis.verdict, Verdict::Found);
+    }
+}
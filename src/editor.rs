@@ -0,0 +1,83 @@
+use crate::config::Config;
+use crate::kind::detect_kind;
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+use std::process::Command;
+
+/// Choose the editor command for `project`: an explicit override, then the
+/// `[editors]` entry for the project's kind, then `$VISUAL`, then `$EDITOR`
+pub fn editor_for(
+    config: &Config,
+    project: &Path,
+    explicit: Option<&str>,
+    var: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    explicit
+        .map(str::to_string)
+        .or_else(|| detect_kind(project).and_then(|kind| config.editors.get(kind).cloned()))
+        .or_else(|| var("VISUAL"))
+        .or_else(|| var("EDITOR"))
+        .filter(|editor| !editor.trim().is_empty())
+}
+
+/// Launch `editor` (which may include arguments, e.g. `code -n`) on `project`
+pub fn open_in_editor(editor: &str, project: &Path) -> Result<()> {
+    let mut words = editor.split_whitespace();
+    let program = words.next().context("Empty editor command")?;
+
+    let status = Command::new(program)
+        .args(words)
+        .arg(project)
+        .current_dir(project)
+        .status()
+        .with_context(|| format!("Failed to run editor: {}", program))?;
+
+    if !status.success() {
+        bail!("Editor `{}` exited with {}", editor, status);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_editor_precedence() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("Cargo.toml"), "").unwrap();
+
+        let mut config = Config::default();
+        let env = |name: &str| match name {
+            "VISUAL" => Some("vim".to_string()),
+            "EDITOR" => Some("nano".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(
+            editor_for(&config, temp_dir.path(), None, env).as_deref(),
+            Some("vim")
+        );
+
+        config
+            .editors
+            .insert("rust".to_string(), "nvim".to_string());
+        assert_eq!(
+            editor_for(&config, temp_dir.path(), None, env).as_deref(),
+            Some("nvim")
+        );
+
+        assert_eq!(
+            editor_for(&config, temp_dir.path(), Some("code -n"), env).as_deref(),
+            Some("code -n")
+        );
+
+        assert_eq!(
+            editor_for(&Config::default(), temp_dir.path(), None, |_| None),
+            None
+        );
+    }
+}
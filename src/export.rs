@@ -0,0 +1,165 @@
+use crate::scanner::Project;
+use anyhow::{Context, Result, bail};
+use serde_json::{Map, Value, json};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A file format `pj export` can write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// VS Code multi-root `.code-workspace`
+    VscodeWorkspace,
+    /// Sublime Text `.sublime-project`
+    SublimeProject,
+    /// Tab-separated display name and project root, one per line, for
+    /// opening the projects in a JetBrains IDE
+    Jetbrains,
+}
+
+impl ExportFormat {
+    /// File written when no `--output` is given
+    pub fn default_file_name(&self) -> &'static str {
+        match self {
+            Self::VscodeWorkspace => "pj.code-workspace",
+            Self::SublimeProject => "pj.sublime-project",
+            Self::Jetbrains => "pj-projects.txt",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "vscode-workspace" => Ok(Self::VscodeWorkspace),
+            "sublime-project" => Ok(Self::SublimeProject),
+            "jetbrains" => Ok(Self::Jetbrains),
+            _ => Err(format!(
+                "unknown format '{}' (expected vscode-workspace, sublime-project or jetbrains)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::VscodeWorkspace => "vscode-workspace",
+            Self::SublimeProject => "sublime-project",
+            Self::Jetbrains => "jetbrains",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Render `projects` in `format`, sorted by display path so that reruns only
+/// change the file when the projects do. For the JSON formats, `existing` is
+/// the current file: its `folders` list is replaced and every other setting
+/// kept.
+pub fn render(
+    format: ExportFormat,
+    projects: &[Project],
+    existing: Option<&str>,
+) -> Result<String> {
+    let mut projects: Vec<&Project> = projects.iter().collect();
+    projects.sort_by(|a, b| a.display_path().cmp(b.display_path()));
+
+    match format {
+        ExportFormat::VscodeWorkspace | ExportFormat::SublimeProject => {
+            let mut root = match existing {
+                Some(contents) => match serde_json::from_str(contents)
+                    .context("Existing file is not valid JSON")?
+                {
+                    Value::Object(map) => map,
+                    _ => bail!("Existing file is not a JSON object"),
+                },
+                None => Map::new(),
+            };
+
+            let folders = projects
+                .iter()
+                .map(|p| json!({ "name": p.display_path(), "path": p.path.to_string_lossy() }))
+                .collect();
+            root.insert("folders".to_string(), Value::Array(folders));
+
+            Ok(serde_json::to_string_pretty(&Value::Object(root))? + "\n")
+        }
+        ExportFormat::Jetbrains => Ok(projects
+            .iter()
+            .map(|p| format!("{}\t{}\n", p.display_path(), p.path.display()))
+            .collect()),
+    }
+}
+
+/// Write or refresh `file` with `projects`. Returns false if the file
+/// already listed exactly these projects.
+pub fn export(format: ExportFormat, projects: &[Project], file: &Path) -> Result<bool> {
+    let existing = match fs::read_to_string(file) {
+        Ok(contents) => Some(contents),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => return Err(err).with_context(|| format!("Failed to read {}", file.display())),
+    };
+
+    let contents = render(format, projects, existing.as_deref())
+        .with_context(|| format!("Failed to update {}", file.display()))?;
+    if existing.as_deref() == Some(contents.as_str()) {
+        return Ok(false);
+    }
+
+    fs::write(file, contents).with_context(|| format!("Failed to write {}", file.display()))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn project(path: &str, display_path: &str) -> Project {
+        Project {
+            path: PathBuf::from(path),
+            display_path: display_path.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_vscode_workspace_refresh_keeps_settings() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("team.code-workspace");
+        fs::write(
+            &file,
+            r#"{"folders": [{"path": "/old"}], "settings": {"editor.tabSize": 2}}"#,
+        )
+        .unwrap();
+
+        let projects = vec![project("/p/api", "api"), project("/p/web/app", "web/app")];
+        assert!(export(ExportFormat::VscodeWorkspace, &projects, &file).unwrap());
+
+        let written: Value = serde_json::from_str(&fs::read_to_string(&file).unwrap()).unwrap();
+        assert_eq!(
+            written["folders"],
+            json!([
+                { "name": "api", "path": "/p/api" },
+                { "name": "web/app", "path": "/p/web/app" },
+            ])
+        );
+        assert_eq!(written["settings"]["editor.tabSize"], 2);
+
+        // Nothing changed, nothing written
+        assert!(!export(ExportFormat::VscodeWorkspace, &projects, &file).unwrap());
+    }
+
+    #[test]
+    fn test_render_jetbrains_list() {
+        let projects = vec![project("/p/api", "api")];
+        assert_eq!(
+            render(ExportFormat::Jetbrains, &projects, None).unwrap(),
+            "api\t/p/api\n"
+        );
+    }
+}
@@ -0,0 +1,268 @@
+//! A builder for embedding pj's project discovery and matching in other tools.
+//!
+//! ```no_run
+//! use pj::finder::{ManifestSource, ProjectFinder};
+//!
+//! let finder = ProjectFinder::new()
+//!     .scan_path("~/Projects")
+//!     .markers([".git", "Cargo.toml"])
+//!     .max_depth(3)
+//!     .exclude("node_modules")
+//!     .source(ManifestSource::new("/etc/team-projects.txt"));
+//!
+//! for m in finder.search("api")? {
+//!     println!("{} {}", m.score.total(), m.project.path.display());
+//! }
+//! # Ok::<(), pj::finder::FinderError>(())
+//! ```
+
+use crate::config::{Config, ConfigError, ScanPath};
+use crate::matcher::{Match, Matcher};
+use crate::scanner::{Project, ScanError, scan};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The error type returned by [`ProjectSource::projects`]
+pub type SourceError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A provider of projects other than the filesystem scan, such as a manifest
+/// file or a list produced by another tool
+pub trait ProjectSource {
+    /// Short name used in error messages
+    fn name(&self) -> &str;
+
+    /// All projects this source knows about
+    fn projects(&self) -> Result<Vec<Project>, SourceError>;
+}
+
+/// A fixed list of projects
+impl ProjectSource for Vec<Project> {
+    fn name(&self) -> &str {
+        "list"
+    }
+
+    fn projects(&self) -> Result<Vec<Project>, SourceError> {
+        Ok(self.clone())
+    }
+}
+
+/// A text file listing one project directory per line. Blank lines and
+/// lines starting with `#` are ignored, `~` is expanded and relative paths
+/// are resolved against the file's directory, which display paths are also
+/// relative to.
+#[derive(Debug, Clone)]
+pub struct ManifestSource {
+    path: PathBuf,
+}
+
+impl ManifestSource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl ProjectSource for ManifestSource {
+    fn name(&self) -> &str {
+        "manifest"
+    }
+
+    fn projects(&self) -> Result<Vec<Project>, SourceError> {
+        let contents = fs::read_to_string(&self.path)?;
+        let base = self.path.parent().unwrap_or(Path::new(""));
+
+        Ok(contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let path = base.join(shellexpand::tilde(line).as_ref());
+                Project::new(path, base)
+            })
+            .collect())
+    }
+}
+
+/// Errors from [`ProjectFinder`]
+#[derive(Debug, thiserror::Error)]
+pub enum FinderError {
+    /// The user's pj config could not be loaded
+    #[error("Failed to load pj config")]
+    Config(#[source] ConfigError),
+    /// The filesystem scan failed
+    #[error(transparent)]
+    Scan(#[from] ScanError),
+    /// A [`ProjectSource`] failed
+    #[error("Project source {name} failed: {source}")]
+    Source {
+        name: String,
+        #[source]
+        source: SourceError,
+    },
+}
+
+/// Finds projects by scanning directories and querying extra
+/// [`ProjectSource`]s, and fuzzy-matches them with pj's ranking
+pub struct ProjectFinder {
+    config: Config,
+    sources: Vec<Box<dyn ProjectSource>>,
+}
+
+impl Default for ProjectFinder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProjectFinder {
+    /// A finder with pj's default markers and depth and no scan paths
+    pub fn new() -> Self {
+        Self::from_config(Config {
+            scan_paths: Vec::new(),
+            ..Config::default()
+        })
+    }
+
+    /// A finder using the user's pj config, loaded as `pj` itself does
+    pub fn load() -> Result<Self, FinderError> {
+        let config = Config::load().map_err(FinderError::Config)?;
+        Ok(Self::from_config(config))
+    }
+
+    /// A finder using the scan paths, markers, excludes and ranking of a
+    /// loaded pj config
+    pub fn from_config(config: Config) -> Self {
+        Self {
+            config,
+            sources: Vec::new(),
+        }
+    }
+
+    /// Add a directory to scan
+    pub fn scan_path(mut self, path: impl Into<ScanPath>) -> Self {
+        self.config.scan_paths.push(path.into());
+        self
+    }
+
+    /// Replace the files or directories that mark a project root
+    pub fn markers<I, S>(mut self, markers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.project_markers = markers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// How deep below each scan path to look for projects
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.config.max_depth = max_depth;
+        self
+    }
+
+    /// Skip directories matching a glob: a bare name such as `node_modules`
+    /// matches the directory name, a pattern with `/` the whole path
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.config.exclude.push(pattern.into());
+        self
+    }
+
+    /// Descend into symlinked directories
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.config.follow_symlinks = follow;
+        self
+    }
+
+    /// Add a source of projects besides the filesystem scan
+    pub fn source(mut self, source: impl ProjectSource + 'static) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    /// The config the finder scans with
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Scan the filesystem, then add projects from each source in order. A
+    /// project path already found is not listed again.
+    pub fn find(&self) -> Result<Vec<Project>, FinderError> {
+        let mut projects = scan(&self.config)?.projects;
+        let mut seen: HashSet<PathBuf> = projects.iter().map(|p| p.path.clone()).collect();
+
+        for source in &self.sources {
+            let found = source
+                .projects()
+                .map_err(|source_err| FinderError::Source {
+                    name: source.name().to_string(),
+                    source: source_err,
+                })?;
+            projects.extend(found.into_iter().filter(|p| seen.insert(p.path.clone())));
+        }
+
+        Ok(projects)
+    }
+
+    /// Find projects and rank those matching `pattern`, best first. See
+    /// [`Matcher::find_matches`] for the query syntax.
+    pub fn search(&self, pattern: &str) -> Result<Vec<Match>, FinderError> {
+        let mut matcher = Matcher::with_ranking(self.config.ranking.clone());
+        matcher.add_projects(self.find()?);
+        Ok(matcher.find_scored(pattern))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    struct FailingSource;
+
+    impl ProjectSource for FailingSource {
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        fn projects(&self) -> Result<Vec<Project>, SourceError> {
+            Err("unreachable".into())
+        }
+    }
+
+    #[test]
+    fn test_finder_combines_scan_and_sources() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("api/.git")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("web/node_modules/dep/.git")).unwrap();
+
+        let manifest = temp_dir.path().join("manifest.txt");
+        fs::write(&manifest, "# team projects\n/srv/tools\napi\n").unwrap();
+
+        let finder = ProjectFinder::new()
+            .scan_path(temp_dir.path())
+            .markers([".git"])
+            .exclude("node_modules")
+            .source(ManifestSource::new(&manifest));
+
+        let paths: Vec<PathBuf> = finder.find().unwrap().into_iter().map(|p| p.path).collect();
+        assert_eq!(
+            paths,
+            vec![temp_dir.path().join("api"), PathBuf::from("/srv/tools")]
+        );
+
+        let matches = finder.search("tools").unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].project.path, PathBuf::from("/srv/tools"));
+    }
+
+    #[test]
+    fn test_finder_reports_source_errors() {
+        let finder = ProjectFinder::new().source(FailingSource);
+        let err = finder.find().unwrap_err();
+        assert!(matches!(err, FinderError::Source { ref name, .. } if name == "failing"));
+        assert_eq!(
+            err.to_string(),
+            "Project source failing failed: unreachable"
+        );
+    }
+}
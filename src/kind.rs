@@ -0,0 +1,48 @@
+use std::path::Path;
+
+/// Files that identify a project's kind, checked in order
+const KIND_MARKERS: &[(&str, &str)] = &[
+    ("Cargo.toml", "rust"),
+    ("package.json", "node"),
+    ("go.mod", "go"),
+    ("pyproject.toml", "python"),
+    ("setup.py", "python"),
+    ("requirements.txt", "python"),
+    ("Gemfile", "ruby"),
+    ("mix.exs", "elixir"),
+    ("pom.xml", "java"),
+    ("build.gradle", "java"),
+    ("build.gradle.kts", "kotlin"),
+    ("build.zig", "zig"),
+    ("CMakeLists.txt", "cmake"),
+    ("flake.nix", "nix"),
+];
+
+/// Detect what kind of project `dir` is (e.g. "rust" or "node") from the
+/// files in its root
+pub fn detect_kind(dir: &Path) -> Option<&'static str> {
+    KIND_MARKERS
+        .iter()
+        .find(|(file, _)| dir.join(file).exists())
+        .map(|(_, kind)| *kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_detect_kind() {
+        let temp_dir = TempDir::new().unwrap();
+        assert_eq!(detect_kind(temp_dir.path()), None);
+
+        fs::write(temp_dir.path().join("package.json"), "{}").unwrap();
+        assert_eq!(detect_kind(temp_dir.path()), Some("node"));
+
+        // Earlier entries win when several markers are present
+        fs::write(temp_dir.path().join("Cargo.toml"), "").unwrap();
+        assert_eq!(detect_kind(temp_dir.path()), Some("rust"));
+    }
+}
@@ -1,10 +1,34 @@
 // Library interface for pj - exposes modules for testing and potential reuse
 
+pub mod clone;
 pub mod config;
+#[cfg(unix)]
+pub mod daemon;
+pub mod doctor;
+pub mod editor;
+pub mod export;
+pub mod finder;
+pub mod history;
+pub mod hooks;
+pub mod kind;
 pub mod matcher;
+pub mod mcp;
+pub mod picker;
+pub mod project_file;
+pub mod resolve;
 pub mod scanner;
+pub mod serve;
+pub mod session;
+pub mod state;
+pub mod tags;
+pub mod template;
+pub mod watch;
 
 // Re-export key types for convenience
-pub use config::Config;
-pub use matcher::Matcher;
-pub use scanner::{Project, scan_projects};
+pub use config::{Config, ConfigError, ScanPath};
+pub use finder::{FinderError, ProjectFinder, ProjectSource};
+pub use matcher::{Match, Matcher};
+pub use scanner::{
+    CancelToken, Project, ScanDiagnostic, ScanDiagnosticKind, ScanError, ScanIter, ScanReport,
+    ScanStats, scan, scan_channel, scan_iter, scan_projects,
+};
@@ -1,22 +1,40 @@
-mod config;
-mod matcher;
-mod picker;
-mod scanner;
-
-use anyhow::{Context, Result};
-use clap::Parser;
-use config::Config;
-use matcher::Matcher;
-use picker::InteractivePicker;
-use scanner::scan_projects;
+use anyhow::{Context, Result, bail};
+use clap::{Parser, Subcommand};
+use pj::clone::{Vcs, clone};
+use pj::config::{ConfigSource, LoadedConfig};
+#[cfg(unix)]
+use pj::daemon::{self, Client, Request};
+use pj::doctor::{Verdict, diagnose_path};
+use pj::editor::{editor_for, open_in_editor};
+use pj::export::{self, ExportFormat};
+use pj::history::{self, History, ImportSource, parse_database};
+use pj::hooks::{HookEvent, TrustStore, resolve};
+use pj::matcher::{Match, auto_select, rank};
+use pj::mcp::McpServer;
+use pj::picker::InteractivePicker;
+use pj::project_file::{PROJECT_FILE, ProjectFile};
+use pj::resolve::enclosing_projects;
+use pj::serve::Server;
+use pj::session::{live_sessions, open_session, session_name};
+use pj::state::state_dir;
+use pj::tags::{TagStore, normalize_tag, split_tag_terms};
+use pj::template::{create_project, template_dir};
+use pj::watch::{Change, ProjectWatcher, RESCAN_INTERVAL};
+use pj::{Config, Project, ScanReport, ScanStats, scan};
+use std::collections::BTreeMap;
 use std::fs::{self, File};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 #[derive(Parser, Debug)]
 #[command(name = "pj")]
 #[command(about = "Project Jump - Fast project directory jumper", long_about = None)]
 struct Args {
-    /// Pattern to match against project paths
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Pattern to match against project paths (use `pj -- <pattern>` for a
+    /// pattern that is also a subcommand name)
     pattern: Option<String>,
 
     /// List all matches without interactive picker
@@ -27,28 +45,203 @@ struct Args {
     #[arg(long)]
     init_config: bool,
 
+    /// Report scan problems such as unreadable directories
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// Print per-scan-path statistics and timing instead of jumping
+    #[arg(long)]
+    scan_stats: bool,
+
+    /// Print each match's score components instead of jumping
+    #[arg(long)]
+    explain: bool,
+
+    /// Always show the picker when several projects match, even if one is clearly best
+    #[arg(long, global = true)]
+    pick: bool,
+
+    /// Only consider projects with this tag (repeatable)
+    #[arg(id = "tag_filter", long = "tag", value_name = "TAG", global = true)]
+    tags: Vec<String>,
+
+    /// Use this config file instead of ~/.config/pj/config.toml
+    #[arg(long, value_name = "PATH", global = true)]
+    config: Option<PathBuf>,
+
     /// Set the previous directory (used by shell wrapper)
     #[arg(long, hide = true)]
     set_prev: Option<String>,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Inspect the effective configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Clone a repository into host/org/repo under the clone root and print its path
+    Clone {
+        /// Repository URL or local path
+        url: String,
+
+        /// Clone with jj instead of git
+        #[arg(long)]
+        jj: bool,
+    },
+    /// Create a new project, optionally from a template, and print its path
+    New {
+        /// Project name, optionally with parent directories (e.g. ai/widget)
+        name: String,
+
+        /// Template in ~/.config/pj/templates to copy
+        #[arg(short, long)]
+        template: Option<String>,
+
+        /// Directory to create the project in (defaults to the first scan path)
+        #[arg(long = "in", value_name = "DIR")]
+        parent: Option<PathBuf>,
+    },
+    /// Attach to a tmux or zellij session for a project, creating it if needed
+    Session {
+        /// Pattern to match against project paths
+        pattern: Option<String>,
+
+        /// List projects, marking those with a live session with `*`
+        #[arg(short, long)]
+        list: bool,
+    },
+    /// Open a project in your editor
+    Open {
+        /// Pattern to match against project paths
+        pattern: Option<String>,
+
+        /// Editor command to use instead of the configured one
+        #[arg(short, long)]
+        editor: Option<String>,
+    },
+    /// Add, remove and list project tags
+    Tag {
+        #[command(subcommand)]
+        action: TagAction,
+    },
+    /// Run a command in each matching project
+    Foreach {
+        /// Pattern to match against project paths
+        #[arg(short, long)]
+        pattern: Option<String>,
+
+        /// Command and arguments to run
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Write the matching projects to an editor workspace file, refreshing it if it exists
+    Export {
+        /// Pattern to match against project paths (default: all projects)
+        pattern: Option<String>,
+
+        /// File format: vscode-workspace, sublime-project or jetbrains
+        #[arg(long = "as", value_name = "FORMAT")]
+        format: ExportFormat,
+
+        /// File to write (default: pj.code-workspace, pj.sublime-project or pj-projects.txt)
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Seed pj's visit history from another directory jumper's database
+    Import {
+        /// Database format: zoxide, autojump, z or fasd
+        #[arg(long, value_name = "TOOL")]
+        from: ImportSource,
+
+        /// Database file, e.g. ~/.local/share/zoxide/db.zo or ~/.z
+        file: PathBuf,
+    },
+    /// Approve the hooks in a project's .pj.toml so the shell wrapper runs them
+    Trust {
+        /// Project directory or .pj.toml file (defaults to the current directory)
+        path: Option<PathBuf>,
+
+        /// Withdraw approval instead
+        #[arg(long)]
+        revoke: bool,
+    },
+    /// Print the hook commands for a project (used by shell wrapper)
+    #[command(hide = true)]
+    Hooks {
+        /// Print on_enter hooks for this project
+        #[arg(long, conflicts_with = "leave")]
+        enter: Option<PathBuf>,
+
+        /// Print on_leave hooks for this project
+        #[arg(long)]
+        leave: Option<PathBuf>,
+    },
+    /// Keep the project index and visit history in memory and answer
+    /// queries over a Unix socket, so other pj commands skip the scan
+    #[cfg(unix)]
+    Daemon,
+    /// Print projects as they appear (+) or disappear (-) under the scan paths
+    Watch,
+    /// Answer JSON-RPC requests from an editor plugin
+    Serve {
+        /// Speak newline-delimited JSON-RPC on stdin and stdout
+        #[arg(long, required = true)]
+        stdio: bool,
+    },
+    /// Serve the project catalog to coding agents over the Model Context
+    /// Protocol on stdin and stdout
+    Mcp,
+    /// Print the project containing a path, and any projects it is nested in
+    Which {
+        /// File or directory (defaults to the current directory)
+        path: Option<PathBuf>,
+    },
+    /// Diagnose why a directory is or isn't found as a project
+    Doctor {
+        /// Directory to check (defaults to the current directory)
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum TagAction {
+    /// Tag a project
+    Add {
+        /// Project directory or pattern
+        project: String,
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// Remove tags from a project
+    Rm {
+        /// Project directory or pattern
+        project: String,
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// List a project's tags, or every tag with its projects
+    List {
+        /// Project directory or pattern
+        project: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Print the merged config and where each value came from
+    Show,
+    /// Validate config files and report problems
+    Check,
+}
+
 /// Check if we're in an interactive terminal by checking /dev/tty
 fn is_interactive() -> bool {
     // Try to open /dev/tty - if successful, we're in an interactive terminal
     File::open("/dev/tty").is_ok()
 }
 
-/// Get the state directory for pj
-fn state_dir() -> Result<PathBuf> {
-    let state_dir = dirs::state_dir()
-        .or_else(|| dirs::home_dir().map(|h| h.join(".local/state")))
-        .context("Could not determine state directory")?
-        .join("pj");
-
-    fs::create_dir_all(&state_dir)?;
-    Ok(state_dir)
-}
-
 /// Get the path to the previous directory file
 fn prev_dir_path() -> Result<PathBuf> {
     Ok(state_dir()?.join("prev_dir"))
@@ -69,9 +262,837 @@ fn write_prev_dir(dir: &str) -> Result<()> {
     Ok(())
 }
 
+/// Print the effective config as TOML, annotating each value with its source
+fn config_show(loaded: &LoadedConfig) -> Result<()> {
+    if loaded.files.is_empty() {
+        println!("# No config files loaded; using defaults");
+    }
+    for file in &loaded.files {
+        println!("# Loaded: {}", file.display());
+    }
+
+    let config = &loaded.config;
+    let origins = &loaded.origins;
+    let fields = [
+        (
+            "scan_paths",
+            Some(toml::Value::try_from(&config.scan_paths)?),
+            &origins.scan_paths,
+        ),
+        (
+            "project_markers",
+            Some(toml::Value::try_from(&config.project_markers)?),
+            &origins.project_markers,
+        ),
+        (
+            "max_depth",
+            Some(toml::Value::try_from(config.max_depth)?),
+            &origins.max_depth,
+        ),
+        (
+            "exclude",
+            Some(&config.exclude)
+                .filter(|exclude| !exclude.is_empty())
+                .map(toml::Value::try_from)
+                .transpose()?,
+            &origins.exclude,
+        ),
+        (
+            "strict",
+            Some(toml::Value::try_from(config.strict)?),
+            &origins.strict,
+        ),
+        (
+            "follow_symlinks",
+            Some(toml::Value::try_from(config.follow_symlinks)?),
+            &origins.follow_symlinks,
+        ),
+        (
+            "clone_root",
+            config
+                .clone_root
+                .as_ref()
+                .map(toml::Value::try_from)
+                .transpose()?,
+            &origins.clone_root,
+        ),
+        (
+            "post_create",
+            config
+                .post_create
+                .as_ref()
+                .map(toml::Value::try_from)
+                .transpose()?,
+            &origins.post_create,
+        ),
+        (
+            "session",
+            Some(toml::Value::try_from(&config.session)?),
+            &origins.session,
+        ),
+        (
+            "ranking",
+            Some(toml::Value::try_from(&config.ranking)?),
+            &origins.ranking,
+        ),
+    ];
+
+    for (key, value, source) in fields {
+        match value {
+            Some(value) => println!("{} = {}  # from {}", key, value, source),
+            None => println!("# {} is unset", key),
+        }
+    }
+
+    // Merged from every layer, so each entry has its own source
+    print_merged_table("hooks", &config.hooks, &origins.hooks)?;
+    print_merged_table("editors", &config.editors, &origins.editors)?;
+
+    Ok(())
+}
+
+/// Print each entry of a config table merged across layers as a dotted key,
+/// with the layer it came from
+fn print_merged_table<T: serde::Serialize>(
+    name: &str,
+    table: &BTreeMap<String, T>,
+    origins: &BTreeMap<String, ConfigSource>,
+) -> Result<()> {
+    if table.is_empty() {
+        println!("# {} is unset", name);
+    }
+    for (key, value) in table {
+        let source = origins.get(key).unwrap_or(&ConfigSource::Default);
+        println!(
+            "{}.{} = {}  # from {}",
+            name,
+            toml::Value::from(key.as_str()),
+            toml::Value::try_from(value)?,
+            source
+        );
+    }
+    Ok(())
+}
+
+/// Validate the config, printing errors and warnings. Exits nonzero on errors.
+fn config_check(explicit: Option<&Path>) -> Result<()> {
+    let loaded = match Config::load_report(explicit) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            eprintln!("error: {:#}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let warnings = loaded.config.warnings();
+    for warning in &warnings {
+        eprintln!("warning: {}", warning);
+    }
+
+    if !loaded.unknown_keys.is_empty() {
+        for key in &loaded.unknown_keys {
+            eprintln!("error: {}", key);
+        }
+        std::process::exit(1);
+    }
+
+    println!(
+        "Config OK ({} file(s), {} warning(s))",
+        loaded.files.len(),
+        warnings.len()
+    );
+    Ok(())
+}
+
+/// Report config files, scan paths, shell integration and what pj thinks of `path`
+fn doctor(loaded: &LoadedConfig, path: Option<&Path>) -> Result<()> {
+    let config = &loaded.config;
+
+    println!("Config files:");
+    if loaded.files.is_empty() {
+        println!("  (none, using defaults)");
+    }
+    for file in &loaded.files {
+        println!("  {}", file.display());
+    }
+
+    println!("Scan paths (max_depth = {}):", config.max_depth);
+    for scan_path in &config.scan_paths {
+        let expanded = scan_path.expanded();
+        let status = if expanded.is_dir() { "ok" } else { "missing" };
+        let follow = if config.follows_symlinks(scan_path) {
+            " (follows symlinks)"
+        } else {
+            ""
+        };
+        println!("  {:<8} {}{}", status, expanded.display(), follow);
+    }
+
+    // The shell wrappers set PJ_WRAPPER when they invoke the binary
+    if std::env::var_os("PJ_WRAPPER").is_some() {
+        println!("Shell wrapper: installed");
+    } else {
+        println!("Shell wrapper: not detected (source pj.sh or install pj.fish)");
+    }
+
+    let path = match path {
+        Some(path) => std::path::absolute(path)?,
+        None => std::env::current_dir()?,
+    };
+    let diagnosis = diagnose_path(config, &path);
+
+    println!("Path: {}", diagnosis.path.display());
+    match (&diagnosis.scan_root, diagnosis.depth) {
+        (Some(root), Some(depth)) => {
+            println!("  scan path: {} (depth {})", root.display(), depth)
+        }
+        _ => println!("  scan path: none"),
+    }
+    if diagnosis.markers.is_empty() {
+        println!("  markers:   none");
+    } else {
+        println!("  markers:   {}", diagnosis.markers.join(", "));
+    }
+
+    let result = match &diagnosis.verdict {
+        Verdict::Found => "found as a project".to_string(),
+        Verdict::Missing => "not found: directory does not exist".to_string(),
+        Verdict::NotUnderScanPath => "not found: not inside any scan path".to_string(),
+        Verdict::TooDeep { depth, max_depth } => {
+            format!("not found: depth {} exceeds max_depth {}", depth, max_depth)
+        }
+        Verdict::Excluded { dir, pattern } => {
+            format!("not found: {} matches exclude {:?}", dir.display(), pattern)
+        }
+        Verdict::BehindSymlink(link) => {
+            format!("not found: scan does not follow symlink {}", link.display())
+        }
+        Verdict::NoMarkers => "not found: no project markers present".to_string(),
+    };
+    println!("  result:    {}", result);
+
+    Ok(())
+}
+
+/// Approve (or revoke approval of) a project's .pj.toml
+fn trust(path: Option<&Path>, revoke: bool) -> Result<()> {
+    let path = match path {
+        Some(path) => std::path::absolute(path)?,
+        None => std::env::current_dir()?,
+    };
+    let file = if path.is_dir() {
+        path.join(PROJECT_FILE)
+    } else {
+        path
+    };
+
+    let mut store = TrustStore::open()?;
+    if revoke {
+        if store.revoke(&file) {
+            println!("Revoked trust for {}", file.display());
+        } else {
+            println!("{} was not trusted", file.display());
+        }
+    } else {
+        let project_file = ProjectFile::read(&file)?;
+        store.trust(&file, &project_file.hooks);
+        println!("Trusted {}", file.display());
+    }
+
+    store.save()
+}
+
+/// Print hook commands for the shell wrapper to evaluate
+fn print_hooks(config: &Config, project: &Path, event: HookEvent) -> Result<()> {
+    let resolved = resolve(config, &TrustStore::open()?, project, event)?;
+
+    if let Some(file) = resolved.untrusted {
+        eprintln!(
+            "pj: skipping hooks in {} (not trusted); review it and run `pj trust {}`",
+            file.display(),
+            project.display()
+        );
+    }
+
+    for command in resolved.commands {
+        println!("{}", command);
+    }
+
+    Ok(())
+}
+
+/// Warn when a freshly created project won't show up in scans
+fn warn_if_not_found(config: &Config, dest: &Path) {
+    if diagnose_path(config, dest).verdict != Verdict::Found {
+        eprintln!(
+            "Warning: {} will not be found by pj; run `pj doctor {}`",
+            dest.display(),
+            dest.display()
+        );
+    }
+}
+
+/// Print a table of scan statistics, one row per scan path
+fn print_scan_stats(stats: &[ScanStats]) {
+    println!(
+        "{:>8} {:>8} {:>8} {:>6} {:>7} {:>10}  SCAN PATH",
+        "DIRS", "STATS", "PROJECTS", "EPERM", "BROKEN", "TIME"
+    );
+
+    for s in stats {
+        if !s.exists {
+            println!("{:>8}  {} (missing)", "-", s.root.display());
+            continue;
+        }
+        println!(
+            "{:>8} {:>8} {:>8} {:>6} {:>7} {:>10}  {}",
+            s.dirs_visited,
+            s.stat_calls,
+            s.projects_found,
+            s.permission_errors,
+            s.broken_symlinks,
+            format!("{:.1?}", s.elapsed),
+            s.root.display()
+        );
+        if s.other_errors > 0 {
+            println!("{:>8} other walk error(s)", s.other_errors);
+        }
+    }
+
+    let total: std::time::Duration = stats.iter().map(|s| s.elapsed).sum();
+    println!("Total scan time: {:.1?}", total);
+}
+
+/// Print a table of matches in rank order with their score components
+fn print_explain(matches: &[Match]) {
+    println!(
+        "{:>6} {:>6} {:>6} {:>6} {:>6} {:>6} {:>6}  PROJECT",
+        "TOTAL", "FUZZY", "EXACT", "PREFIX", "DEPTH", "LENGTH", "VISITS"
+    );
+    for m in matches {
+        let s = &m.score;
+        println!(
+            "{:>6} {:>6} {:>6} {:>6} {:>6} {:>6} {:>6}  {}",
+            s.total(),
+            s.fuzzy,
+            s.exact_basename,
+            s.basename_prefix,
+            s.depth,
+            s.length,
+            s.frecency,
+            m.project.display_path()
+        );
+    }
+}
+
+/// Load the layered config, warning about keys that were ignored
+fn load_config_report(explicit: Option<&Path>) -> Result<LoadedConfig> {
+    let loaded = Config::load_report(explicit)?;
+    for key in &loaded.unknown_keys {
+        eprintln!("warning: {}", key);
+    }
+    Ok(loaded)
+}
+
+/// Load the config (see [`load_config_report`])
+fn load_config(explicit: Option<&Path>) -> Result<Config> {
+    Ok(load_config_report(explicit)?.config)
+}
+
+/// Where the projects come from: a scan by this process, or a running
+/// `pj daemon`, which also does the matching and ranking
+enum Projects {
+    Scanned(ScanReport),
+    #[cfg(unix)]
+    Daemon(Client),
+}
+
+impl Projects {
+    /// Every project, unranked
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn all(&mut self, config: &Config) -> Result<Vec<Project>> {
+        #[cfg(unix)]
+        if let Projects::Daemon(client) = self {
+            match client.list() {
+                Ok(projects) => return Ok(projects),
+                Err(err) => self.fall_back(config, err)?,
+            }
+        }
+        Ok(self.report().projects.clone())
+    }
+
+    /// Scan locally after the daemon failed or timed out
+    #[cfg(unix)]
+    fn fall_back(&mut self, config: &Config, err: anyhow::Error) -> Result<()> {
+        eprintln!("warning: {:#}; scanning instead", err);
+        *self = Projects::Scanned(scan(config)?);
+        Ok(())
+    }
+
+    fn report(&self) -> &ScanReport {
+        match self {
+            Projects::Scanned(report) => report,
+            #[cfg(unix)]
+            Projects::Daemon(_) => unreachable!("daemon projects have no scan report"),
+        }
+    }
+}
+
+/// Load the config and find the projects, reporting diagnostics if asked.
+/// The projects come from `pj daemon` when it is running with the same
+/// config, unless strict mode, scan stats or diagnostics call for a fresh
+/// scan.
+fn load_projects(args: &Args) -> Result<(Config, Projects)> {
+    let config = load_config(args.config.as_deref())?;
+
+    #[cfg(unix)]
+    if !config.strict
+        && !args.scan_stats
+        && !args.verbose
+        && let Some(client) = Client::connect_for(&config)
+    {
+        return Ok((config, Projects::Daemon(client)));
+    }
+
+    let report = scan(&config)?;
+
+    if args.verbose {
+        for diagnostic in &report.diagnostics {
+            eprintln!("Warning: {}", diagnostic);
+        }
+    }
+
+    Ok((config, Projects::Scanned(report)))
+}
+
+/// Narrow the projects to those carrying every `--tag` and `#tag` term in
+/// `pattern`, then fuzzy-match and rank the rest of the pattern
+fn find_matches(
+    args: &Args,
+    config: &Config,
+    projects: &mut Projects,
+    pattern: Option<&str>,
+) -> Result<Vec<Match>> {
+    #[cfg(unix)]
+    if let Projects::Daemon(client) = projects {
+        let mut query = pattern.unwrap_or_default().to_string();
+        for tag in &args.tags {
+            query.push_str(" #");
+            query.push_str(&normalize_tag(tag));
+        }
+        match client.query(&query) {
+            Ok(matches) => return Ok(matches),
+            Err(err) => projects.fall_back(config, err)?,
+        }
+    }
+    let report = projects.report();
+
+    let (mut tags, rest) = split_tag_terms(pattern.unwrap_or_default());
+    tags.extend(args.tags.iter().map(|t| normalize_tag(t)));
+
+    let projects = if tags.is_empty() {
+        report.projects.clone()
+    } else {
+        TagStore::open()?.filter(report.projects.clone(), &tags)
+    };
+
+    // A broken history shouldn't stop the jump, only the frecency boost
+    let history = History::open().unwrap_or_else(|err| {
+        eprintln!("warning: {:#}", err);
+        History::default()
+    });
+    Ok(rank(projects, &rest, &config.ranking, history))
+}
+
+/// [`find_matches`] without the scores
+fn find_projects(
+    args: &Args,
+    config: &Config,
+    projects: &mut Projects,
+    pattern: Option<&str>,
+) -> Result<Vec<Project>> {
+    Ok(find_matches(args, config, projects, pattern)?
+        .into_iter()
+        .map(|m| m.project)
+        .collect())
+}
+
+/// Pick a single project for commands that act on one: the only or clearly
+/// best match for `pattern`, or the picker's choice when several match
+fn select_project(
+    args: &Args,
+    config: &Config,
+    projects: &mut Projects,
+    pattern: Option<&str>,
+) -> Result<Option<Project>> {
+    let candidates = find_matches(args, config, projects, pattern)?;
+
+    if candidates.is_empty() {
+        bail!("No matches found for: {}", pattern.unwrap_or_default());
+    }
+    if !args.pick
+        && let Some(best) = auto_select(&candidates, &config.ranking)
+    {
+        return Ok(Some(best.project.clone()));
+    }
+    if !is_interactive() {
+        bail!(
+            "{} projects match; use a more specific pattern",
+            candidates.len()
+        );
+    }
+
+    let projects = candidates.into_iter().map(|m| m.project).collect();
+    Ok(InteractivePicker::new(projects).pick()?)
+}
+
+/// Resolve a project and open a multiplexer session for it, or list projects
+/// marking those with live sessions
+fn session(args: &Args, pattern: Option<&str>, list: bool) -> Result<()> {
+    let (config, mut projects) = load_projects(args)?;
+
+    if list {
+        let live = live_sessions(config.session.multiplexer)?;
+        for project in find_projects(args, &config, &mut projects, pattern)? {
+            let marker = if live.contains(&session_name(&project)) {
+                "*"
+            } else {
+                " "
+            };
+            println!("{} {}", marker, project.path.display());
+        }
+        return Ok(());
+    }
+
+    match select_project(args, &config, &mut projects, pattern)? {
+        Some(project) => open_session(&config.session, &project),
+        None => std::process::exit(1),
+    }
+}
+
+/// Resolve a project and launch an editor on it
+fn open(args: &Args, pattern: Option<&str>, editor: Option<&str>) -> Result<()> {
+    let (config, mut projects) = load_projects(args)?;
+
+    let Some(project) = select_project(args, &config, &mut projects, pattern)? else {
+        std::process::exit(1);
+    };
+
+    let editor = editor_for(&config, &project.path, editor, |name| {
+        std::env::var(name).ok()
+    })
+    .context("No editor configured; set $VISUAL or $EDITOR, or pass --editor")?;
+
+    open_in_editor(&editor, &project.path)
+}
+
+/// Resolve a `pj tag` project argument: the path of a scanned project, or
+/// else a pattern
+fn tag_target(
+    args: &Args,
+    config: &Config,
+    projects: &mut Projects,
+    project: &str,
+) -> Result<PathBuf> {
+    let path = Path::new(project);
+    if path.is_dir() {
+        let absolute = std::path::absolute(path)?;
+        let canonical = absolute.canonicalize()?;
+        if let Some(found) = projects
+            .all(config)?
+            .iter()
+            .find(|p| p.path == absolute || p.path == canonical)
+        {
+            return Ok(found.path.clone());
+        }
+    }
+
+    match select_project(args, config, projects, Some(project))? {
+        Some(project) => Ok(project.path),
+        None => std::process::exit(1),
+    }
+}
+
+fn tag(args: &Args, action: &TagAction) -> Result<()> {
+    let (config, mut projects) = load_projects(args)?;
+    let mut store = TagStore::open()?;
+
+    match action {
+        TagAction::Add { project, tags } => {
+            let path = tag_target(args, &config, &mut projects, project)?;
+            for tag in tags {
+                store.add(&path, tag);
+            }
+            store.save()
+        }
+        TagAction::Rm { project, tags } => {
+            let path = tag_target(args, &config, &mut projects, project)?;
+            for tag in tags {
+                if !store.remove(&path, tag) {
+                    eprintln!("{} is not tagged {}", path.display(), normalize_tag(tag));
+                }
+            }
+            store.save()
+        }
+        TagAction::List {
+            project: Some(project),
+        } => {
+            let path = tag_target(args, &config, &mut projects, project)?;
+            for tag in store.tags_for(&path) {
+                println!("{}", tag);
+            }
+            Ok(())
+        }
+        TagAction::List { project: None } => {
+            for (tag, paths) in store.all_tags(&projects.all(&config)?) {
+                println!("{}", tag);
+                for path in paths {
+                    println!("  {}", path.display());
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Run `command` in every project matching the pattern and tags. Keeps going
+/// after failures, but exits nonzero if any run failed.
+fn foreach(args: &Args, pattern: Option<&str>, command: &[String]) -> Result<()> {
+    let (config, mut projects) = load_projects(args)?;
+    let projects = find_projects(args, &config, &mut projects, pattern)?;
+    let mut failures = 0;
+
+    for project in &projects {
+        eprintln!("==> {}", project.path.display());
+
+        let status = std::process::Command::new(&command[0])
+            .args(&command[1..])
+            .current_dir(&project.path)
+            .status()
+            .with_context(|| format!("Failed to run {}", command[0]))?;
+
+        if !status.success() {
+            eprintln!("==> {} failed ({})", project.path.display(), status);
+            failures += 1;
+        }
+    }
+
+    if failures > 0 {
+        bail!(
+            "Command failed in {} of {} projects",
+            failures,
+            projects.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Write the projects matching the pattern and tags to a workspace file
+fn export(
+    args: &Args,
+    pattern: Option<&str>,
+    format: ExportFormat,
+    output: Option<&Path>,
+) -> Result<()> {
+    let (config, mut projects) = load_projects(args)?;
+    let projects = find_projects(args, &config, &mut projects, pattern)?;
+    if projects.is_empty() {
+        bail!("No matches found for: {}", pattern.unwrap_or_default());
+    }
+
+    let file = output.unwrap_or(Path::new(format.default_file_name()));
+    if export::export(format, &projects, file)? {
+        println!("Wrote {} projects to {}", projects.len(), file.display());
+    } else {
+        println!("{} is up to date", file.display());
+    }
+
+    Ok(())
+}
+
+/// Credit the directories in another tool's database to their projects
+fn import(args: &Args, source: ImportSource, file: &Path) -> Result<()> {
+    let data = std::fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let entries = parse_database(source, &data)
+        .with_context(|| format!("Failed to parse {} database {}", source, file.display()))?;
+
+    let (config, mut projects) = load_projects(args)?;
+    let mut history = History::open()?;
+    let result = history::import(&mut history, &projects.all(&config)?, entries);
+    history.save()?;
+
+    println!(
+        "Imported {} {} entries into {} projects",
+        result.imported, source, result.projects
+    );
+    if !result.skipped.is_empty() {
+        println!(
+            "Skipped {} paths outside any project{}",
+            result.skipped.len(),
+            if args.verbose { ":" } else { " (-v to list)" }
+        );
+        if args.verbose {
+            for path in &result.skipped {
+                println!("  {}", path.display());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the innermost project containing `path`: its root on the first line
+/// for scripts, then its details and the projects it is nested in
+fn which(config: &Config, path: Option<&Path>) -> Result<()> {
+    let path = match path {
+        Some(path) => std::path::absolute(path)?,
+        None => std::env::current_dir()?,
+    };
+
+    let chain = enclosing_projects(config, &path);
+    let Some(innermost) = chain.first() else {
+        bail!("Not inside a project: {}", path.display());
+    };
+
+    println!("{}", innermost.project.path.display());
+    println!("  display path: {}", innermost.project.display_path());
+    match &innermost.scan_root {
+        Some(root) => println!("  scan path:    {}", root.display()),
+        None => println!("  scan path:    none"),
+    }
+    println!("  markers:      {}", innermost.markers.join(", "));
+    for outer in &chain[1..] {
+        println!("  nested in:    {}", outer.project.path.display());
+    }
+
+    Ok(())
+}
+
+/// Watch the scan paths, printing each project that appears or disappears
+fn watch(config: &Config) -> Result<()> {
+    let mut watcher = ProjectWatcher::new(config.clone())?;
+    eprintln!("Watching {} projects", watcher.projects().len());
+    if !watcher.is_complete() {
+        eprintln!(
+            "Warning: can't watch every directory (raise fs.inotify.max_user_watches?); \
+             rescanning every {} seconds instead",
+            RESCAN_INTERVAL.as_secs()
+        );
+    }
+
+    let mut last_scan = Instant::now();
+    loop {
+        let changes = if !watcher.is_complete() && last_scan.elapsed() >= RESCAN_INTERVAL {
+            last_scan = Instant::now();
+            watcher.rescan()?
+        } else {
+            watcher.poll(Duration::from_secs(1))?
+        };
+
+        for change in changes {
+            match change {
+                Change::Added(project) => println!("+ {}", project.path.display()),
+                Change::Removed(project) => println!("- {}", project.path.display()),
+            }
+        }
+    }
+}
+
+/// Add a jump to `project` to the visit history. This is best-effort: a
+/// failure is only a warning, since it must not stop the jump.
+fn record_visit(project: &Path) {
+    if let Err(err) = try_record_visit(project) {
+        eprintln!("warning: failed to record visit: {:#}", err);
+    }
+}
+
+/// Add a jump to `project` to the visit history, through `pj daemon` when it
+/// is running so its in-memory copy stays current
+fn try_record_visit(project: &Path) -> Result<()> {
+    #[cfg(unix)]
+    if let Some(mut client) = Client::connect() {
+        let request = Request::RecordVisit {
+            path: project.to_path_buf(),
+        };
+        if client.request(&request).is_ok() {
+            return Ok(());
+        }
+    }
+
+    let mut history = History::open()?;
+    history.record(project);
+    history.save()
+}
+
 fn main() -> Result<()> {
     let args = Args::parse();
+    let explicit = args.config.as_deref();
+
+    match &args.command {
+        Some(Command::Config { action }) => match action {
+            ConfigAction::Show => config_show(&load_config_report(explicit)?),
+            ConfigAction::Check => config_check(explicit),
+        },
+        Some(Command::Clone { url, jj }) => {
+            let config = load_config(explicit)?;
+            let vcs = if *jj { Vcs::Jj } else { Vcs::Git };
+            let dest = clone(&config, url, vcs)?;
+
+            warn_if_not_found(&config, &dest);
+            println!("{}", dest.display());
+            Ok(())
+        }
+        Some(Command::New {
+            name,
+            template,
+            parent,
+        }) => {
+            let config = load_config(explicit)?;
+            let template = template.as_deref().map(template_dir).transpose()?;
+            let parent = parent.as_deref().map(std::path::absolute).transpose()?;
+            let dest = create_project(&config, name, template.as_deref(), parent.as_deref())?;
+
+            warn_if_not_found(&config, &dest);
+            println!("{}", dest.display());
+            Ok(())
+        }
+        Some(Command::Session { pattern, list }) => session(&args, pattern.as_deref(), *list),
+        Some(Command::Open { pattern, editor }) => {
+            open(&args, pattern.as_deref(), editor.as_deref())
+        }
+        Some(Command::Tag { action }) => tag(&args, action),
+        Some(Command::Foreach { pattern, command }) => foreach(&args, pattern.as_deref(), command),
+        Some(Command::Export {
+            pattern,
+            format,
+            output,
+        }) => export(&args, pattern.as_deref(), *format, output.as_deref()),
+        Some(Command::Import { from, file }) => import(&args, *from, file),
+        Some(Command::Trust { path, revoke }) => trust(path.as_deref(), *revoke),
+        Some(Command::Hooks { enter, leave }) => {
+            let config = load_config(explicit)?;
+            if let Some(project) = enter {
+                print_hooks(&config, project, HookEvent::Enter)?;
+            }
+            if let Some(project) = leave {
+                print_hooks(&config, project, HookEvent::Leave)?;
+            }
+            Ok(())
+        }
+        #[cfg(unix)]
+        Some(Command::Daemon) => daemon::run(explicit),
+        Some(Command::Watch) => watch(&load_config(explicit)?),
+        Some(Command::Serve { .. }) => Server::open(load_config(explicit)?)?.serve_stdio(),
+        Some(Command::Mcp) => McpServer::open(load_config(explicit)?)?.serve_stdio(),
+        Some(Command::Which { path }) => which(&load_config(explicit)?, path.as_deref()),
+        Some(Command::Doctor { path }) => doctor(&load_config_report(explicit)?, path.as_deref()),
+        None => jump(args),
+    }
+}
 
+/// The default command: print the path of the project to jump to
+fn jump(args: Args) -> Result<()> {
     // Handle setting previous directory
     if let Some(prev) = args.set_prev {
         write_prev_dir(&prev)?;
@@ -85,19 +1106,24 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
-    // Load configuration
-    let config = Config::load()?;
+    // Load configuration and scan for projects
+    let (config, mut projects) = load_projects(&args)?;
 
-    // Scan for projects
-    let projects = scan_projects(&config)?;
+    // Scan stats always come from a local scan
+    if args.scan_stats {
+        print_scan_stats(&projects.report().stats);
+        return Ok(());
+    }
 
-    if projects.is_empty() {
+    if matches!(&projects, Projects::Scanned(report) if report.projects.is_empty()) {
         eprintln!("No projects found in configured scan paths");
         std::process::exit(1);
     }
 
-    // Handle pattern matching
-    if let Some(pattern) = args.pattern {
+    // Handle pattern matching (a tag filter alone counts as a pattern)
+    if args.pattern.is_some() || !args.tags.is_empty() {
+        let pattern = args.pattern.clone().unwrap_or_default();
+
         // Special case: "pj -" jumps to previous directory
         if pattern == "-" {
             if let Some(prev) = read_prev_dir() {
@@ -114,9 +1140,17 @@ fn main() -> Result<()> {
             }
         }
 
-        let mut matcher = Matcher::new();
-        matcher.add_projects(projects);
-        let matches = matcher.find_matches(&pattern);
+        if args.explain {
+            print_explain(&find_matches(
+                &args,
+                &config,
+                &mut projects,
+                Some(&pattern),
+            )?);
+            return Ok(());
+        }
+
+        let matches = find_matches(&args, &config, &mut projects, Some(&pattern))?;
 
         match matches.len() {
             0 => {
@@ -125,20 +1159,31 @@ fn main() -> Result<()> {
             }
             1 => {
                 // Single match - print the path
-                println!("{}", matches[0].path.display());
+                println!("{}", matches[0].project.path.display());
+                record_visit(&matches[0].project.path);
             }
             _ => {
                 // Multiple matches - show interactive picker or list
                 if args.list || !is_interactive() {
                     // List mode or non-interactive - print all matches
                     for m in matches {
-                        println!("{}", m.path.display());
+                        println!("{}", m.project.path.display());
                     }
+                } else if !args.pick
+                    && let Some(best) = auto_select(&matches, &config.ranking)
+                {
+                    // The best match is clearly ahead - jump without asking
+                    println!("{}", best.project.path.display());
+                    record_visit(&best.project.path);
                 } else {
                     // Interactive mode - show picker
-                    let picker = InteractivePicker::new(matches);
+                    let projects = matches.into_iter().map(|m| m.project).collect();
+                    let picker = InteractivePicker::new(projects);
                     match picker.pick()? {
-                        Some(project) => println!("{}", project.path.display()),
+                        Some(project) => {
+                            println!("{}", project.path.display());
+                            record_visit(&project.path);
+                        }
                         None => std::process::exit(1),
                     }
                 }
@@ -146,6 +1191,7 @@ fn main() -> Result<()> {
         }
     } else {
         // No pattern - show interactive picker or list all
+        let projects = projects.all(&config)?;
         if args.list || !is_interactive() {
             // List mode or non-interactive - print all projects
             for project in projects {
@@ -155,7 +1201,10 @@ fn main() -> Result<()> {
             // Interactive mode - show picker
             let picker = InteractivePicker::new(projects);
             match picker.pick()? {
-                Some(project) => println!("{}", project.path.display()),
+                Some(project) => {
+                    println!("{}", project.path.display());
+                    record_visit(&project.path);
+                }
                 None => std::process::exit(1),
             }
         }
@@ -1,9 +1,141 @@
+use crate::history::{self, History};
 use crate::scanner::Project;
+use nucleo::pattern::{CaseMatching, Normalization};
 use nucleo::{Config as NucleoConfig, Nucleo, Utf32String};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
+/// Column holding the full display path
+const PATH_COLUMN: usize = 0;
+/// Column holding only the last path segment, for `/term` queries
+const BASENAME_COLUMN: usize = 1;
+
+/// The `[ranking]` config table: adjustments applied on top of the fuzzy
+/// score so that `pj app` prefers a project called `app` over
+/// `apps/legacy/some-app-tools`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RankingConfig {
+    /// Added when a query term equals the project's last path segment
+    pub exact_basename: i64,
+    /// Added when the last path segment starts with a query term
+    pub basename_prefix: i64,
+    /// Subtracted for each path segment below the scan path
+    pub depth_penalty: i64,
+    /// Subtracted for each character of the display path
+    pub length_penalty: i64,
+    /// Multiplied by the log of a project's frecency (visit rank weighted by
+    /// recency), so often and recently visited projects rank higher
+    pub frecency: i64,
+    /// Skip the picker when the best match is clearly ahead of the rest
+    pub auto_select: bool,
+    /// How far the best total score must lead the runner-up to auto-select
+    pub auto_select_margin: i64,
+}
+
+impl Default for RankingConfig {
+    fn default() -> Self {
+        Self {
+            exact_basename: 200,
+            basename_prefix: 50,
+            depth_penalty: 10,
+            length_penalty: 1,
+            frecency: 20,
+            auto_select: true,
+            auto_select_margin: 100,
+        }
+    }
+}
+
+/// The components of a match's score, as shown by `--explain`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Score {
+    /// nucleo's fuzzy score, summed over the path and basename terms
+    pub fuzzy: i64,
+    pub exact_basename: i64,
+    pub basename_prefix: i64,
+    /// Negative: the depth penalty
+    pub depth: i64,
+    /// Negative: the length penalty
+    pub length: i64,
+    /// The visit history boost
+    pub frecency: i64,
+}
+
+impl Score {
+    pub fn total(&self) -> i64 {
+        // The weights are user-configurable, so don't overflow on extreme ones
+        [
+            self.exact_basename,
+            self.basename_prefix,
+            self.depth,
+            self.length,
+            self.frecency,
+        ]
+        .into_iter()
+        .fold(self.fuzzy, i64::saturating_add)
+    }
+}
+
+/// A project matched by a query, with its score
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub project: Project,
+    pub score: Score,
+    /// Whether a query term equals the project's last path segment
+    pub exact: bool,
+    /// Character positions in the display path matched by the query, in
+    /// ascending order, for highlighting
+    pub indices: Vec<u32>,
+}
+
+/// The match to use without asking when the ranking leaves no doubt: the
+/// only match, the only exact basename match, or a match whose total score
+/// leads the runner-up by at least `auto_select_margin`. `matches` must be in
+/// rank order, as returned by [`Matcher::find_scored`].
+pub fn auto_select<'a>(matches: &'a [Match], ranking: &RankingConfig) -> Option<&'a Match> {
+    match matches {
+        [] => None,
+        [only] => Some(only),
+        [best, runner_up, ..] => {
+            let unique_exact = best.exact && !matches[1..].iter().any(|m| m.exact);
+            let margin = best.score.total() - runner_up.score.total();
+            (ranking.auto_select && (unique_exact || margin >= ranking.auto_select_margin))
+                .then_some(best)
+        }
+    }
+}
+
+/// Rank `projects` against `pattern` (see [`Matcher::find_matches`]) with
+/// `ranking`, boosted by their frecency in `history`. An empty pattern keeps
+/// every project, in order and unscored.
+pub fn rank(
+    projects: Vec<Project>,
+    pattern: &str,
+    ranking: &RankingConfig,
+    history: History,
+) -> Vec<Match> {
+    if pattern.trim().is_empty() {
+        return projects
+            .into_iter()
+            .map(|project| Match {
+                project,
+                score: Score::default(),
+                exact: false,
+                indices: Vec::new(),
+            })
+            .collect();
+    }
+
+    let mut matcher = Matcher::with_ranking(ranking.clone()).with_history(history);
+    matcher.add_projects(projects);
+    matcher.find_scored(pattern)
+}
+
 pub struct Matcher {
     nucleo: Nucleo<Project>,
+    ranking: RankingConfig,
+    history: History,
 }
 
 impl Default for Matcher {
@@ -14,14 +146,29 @@ impl Default for Matcher {
 
 impl Matcher {
     pub fn new() -> Self {
+        Self::with_ranking(RankingConfig::default())
+    }
+
+    /// Create a matcher that ranks matches with `ranking`
+    pub fn with_ranking(ranking: RankingConfig) -> Self {
         let nucleo = Nucleo::new(
             NucleoConfig::DEFAULT,
             Arc::new(|| {}),
             None,
-            1, // number of threads
+            2, // columns: display path and basename
         );
 
-        Self { nucleo }
+        Self {
+            nucleo,
+            ranking,
+            history: History::default(),
+        }
+    }
+
+    /// Boost matches by their frecency in `history`
+    pub fn with_history(mut self, history: History) -> Self {
+        self.history = history;
+        self
     }
 
     /// Add projects to the matcher
@@ -30,34 +177,202 @@ impl Matcher {
 
         for project in projects {
             injector.push(project, |proj, cols| {
-                cols[0] = Utf32String::from(proj.display_path());
+                cols[PATH_COLUMN] = Utf32String::from(proj.display_path());
+                cols[BASENAME_COLUMN] = Utf32String::from(basename(proj));
             });
         }
     }
 
-    /// Perform fuzzy matching and return sorted results
+    /// Perform fuzzy matching and return sorted results.
+    ///
+    /// Space-separated terms must all match and accept fzf's operators:
+    /// `'exact`, `^prefix`, `suffix$` and `!negate`. A term starting with
+    /// `/` (or `!/` when negated) only matches the last path segment.
     pub fn find_matches(&mut self, pattern: &str) -> Vec<Project> {
-        // Set the pattern
-        self.nucleo.pattern.reparse(
-            0,
-            pattern,
-            nucleo::pattern::CaseMatching::Smart,
-            nucleo::pattern::Normalization::Smart,
-            false,
-        );
+        self.find_scored(pattern)
+            .into_iter()
+            .map(|m| m.project)
+            .collect()
+    }
 
-        // Tick to process matches
-        self.nucleo.tick(10);
+    /// Like [`Matcher::find_matches`], keeping each match's score
+    /// components. Matches are ordered by total score, then by shallower
+    /// depth and shorter path.
+    pub fn find_scored(&mut self, pattern: &str) -> Vec<Match> {
+        let (path_terms, basename_terms) = split_query(pattern);
+        let needles: Vec<String> = path_terms
+            .iter()
+            .chain(&basename_terms)
+            .filter_map(|term| plain_term(term))
+            .collect();
+
+        for (column, terms) in [(PATH_COLUMN, path_terms), (BASENAME_COLUMN, basename_terms)] {
+            self.nucleo.pattern.reparse(
+                column,
+                &terms.join(" "),
+                CaseMatching::Smart,
+                Normalization::Smart,
+                false,
+            );
+        }
 
-        // Get the snapshot of matches
+        // Tick until every item has been matched
+        while self.nucleo.tick(10).running {}
+
+        let now = history::now();
         let snapshot = self.nucleo.snapshot();
+        let mut scorer = nucleo::Matcher::new(NucleoConfig::DEFAULT);
 
-        // Collect matched items
-        snapshot
+        let mut matches: Vec<Match> = snapshot
             .matched_items(..snapshot.matched_item_count())
-            .map(|item| item.data.clone())
-            .collect()
+            .map(|item| {
+                let project = item.data;
+                let mut indices = Vec::new();
+                let mut fuzzy = 0;
+                for column in [PATH_COLUMN, BASENAME_COLUMN] {
+                    let mut column_indices = Vec::new();
+                    fuzzy += snapshot
+                        .pattern()
+                        .column_pattern(column)
+                        .indices(
+                            item.matcher_columns[column].slice(..),
+                            &mut scorer,
+                            &mut column_indices,
+                        )
+                        .map_or(0, i64::from);
+                    indices.extend(display_indices(project, column, column_indices));
+                }
+                indices.sort_unstable();
+                indices.dedup();
+
+                Match {
+                    exact: is_exact(project, &needles),
+                    score: self.score(project, fuzzy, &needles, now),
+                    project: project.clone(),
+                    indices,
+                }
+            })
+            .collect();
+
+        matches.sort_by_key(|m| {
+            (
+                std::cmp::Reverse(m.score.total()),
+                depth(&m.project),
+                m.project.display_path().len(),
+            )
+        });
+        matches
+    }
+
+    /// Apply the ranking adjustments to a fuzzy score
+    fn score(&self, project: &Project, fuzzy: i64, needles: &[String], now: u64) -> Score {
+        let name = basename(project).to_lowercase();
+        let exact = is_exact(project, needles);
+        let prefix = !exact && needles.iter().any(|n| name.starts_with(n.as_str()));
+        let frecency = self.history.frecency(&project.path, now);
+
+        Score {
+            fuzzy,
+            exact_basename: if exact {
+                self.ranking.exact_basename
+            } else {
+                0
+            },
+            basename_prefix: if prefix {
+                self.ranking.basename_prefix
+            } else {
+                0
+            },
+            depth: (depth(project) as i64)
+                .saturating_mul(self.ranking.depth_penalty)
+                .saturating_neg(),
+            length: (project.display_path().chars().count() as i64)
+                .saturating_mul(self.ranking.length_penalty)
+                .saturating_neg(),
+            frecency: (self.ranking.frecency as f64 * frecency.ln_1p()).round() as i64,
+        }
+    }
+}
+
+/// Whether one of the query's plain terms equals the project's basename
+fn is_exact(project: &Project, needles: &[String]) -> bool {
+    needles.contains(&basename(project).to_lowercase())
+}
+
+/// Map positions matched in `column` to positions in the display path. The
+/// basename column is the display path's last segment, unless the project is
+/// the scan path itself and its display path doesn't contain it.
+fn display_indices(project: &Project, column: usize, indices: Vec<u32>) -> Vec<u32> {
+    if column == PATH_COLUMN {
+        return indices;
+    }
+    let display = project.display_path();
+    let name = basename(project);
+    if !display.ends_with(name) {
+        return Vec::new();
+    }
+    let offset = (display.chars().count() - name.chars().count()) as u32;
+    indices.into_iter().map(|i| i + offset).collect()
+}
+
+/// Number of path segments below the scan path, counting from zero
+fn depth(project: &Project) -> usize {
+    project.display_path().matches('/').count()
+}
+
+/// The literal text of a positive query term with its operators removed,
+/// lowercased for comparison with basenames
+fn plain_term(term: &str) -> Option<String> {
+    if term.starts_with('!') {
+        return None;
     }
+    let term = term.strip_prefix(['\'', '^']).unwrap_or(term);
+    let term = term.strip_suffix('$').unwrap_or(term);
+    let term = term.replace("\\ ", " ").to_lowercase();
+    (!term.is_empty()).then_some(term)
+}
+
+/// The last segment of a project's path
+fn basename(project: &Project) -> &str {
+    project
+        .path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(project.display_path())
+}
+
+/// Split a query into terms for the full path and `/`-prefixed terms for the
+/// basename, with the `/` removed. Backslash-escaped spaces stay in a term.
+fn split_query(pattern: &str) -> (Vec<String>, Vec<String>) {
+    let mut terms = Vec::new();
+    let mut term = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                term.push(c);
+                if let Some(next) = chars.next() {
+                    term.push(next);
+                }
+            }
+            c if c.is_whitespace() => terms.push(std::mem::take(&mut term)),
+            c => term.push(c),
+        }
+    }
+    terms.push(term);
+
+    let mut path_terms = Vec::new();
+    let mut basename_terms = Vec::new();
+    for term in terms.into_iter().filter(|t| !t.is_empty()) {
+        if let Some(rest) = term.strip_prefix('/').filter(|r| !r.is_empty()) {
+            basename_terms.push(rest.to_string());
+        } else if let Some(rest) = term.strip_prefix("!/").filter(|r| !r.is_empty()) {
+            basename_terms.push(format!("!{}", rest));
+        } else {
+            path_terms.push(term);
+        }
+    }
+    (path_terms, basename_terms)
 }
 
 #[cfg(test)]
@@ -72,6 +387,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_score_total_saturates() {
+        let score = Score {
+            fuzzy: 100,
+            frecency: i64::MAX,
+            ..Score::default()
+        };
+        assert_eq!(score.total(), i64::MAX);
+    }
+
     #[test]
     fn test_matcher_exact_match() {
         let mut matcher = Matcher::new();
@@ -215,4 +540,186 @@ mod tests {
                 .any(|p| p.display_path == "my-awesome-project")
         );
     }
+
+    fn match_paths(pattern: &str) -> Vec<String> {
+        let mut matcher = Matcher::new();
+        matcher.add_projects(vec![
+            create_test_project("/p/api", "api"),
+            create_test_project("/p/api-old", "api-old"),
+            create_test_project("/p/svc-auth", "svc-auth"),
+            create_test_project("/p/web/main", "web/main"),
+            create_test_project("/p/web/feature", "web/feature"),
+            create_test_project("/p/maintenance", "maintenance"),
+            create_test_project("/p/mobile/app-info", "mobile/app-info"),
+        ]);
+        let mut paths: Vec<String> = matcher
+            .find_matches(pattern)
+            .into_iter()
+            .map(|p| p.display_path)
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_match_indices() {
+        let mut matcher = Matcher::new();
+        matcher.add_projects(vec![create_test_project("/p/web/api", "web/api")]);
+
+        let matches = matcher.find_scored("wb /api !old");
+        assert_eq!(matches[0].indices, vec![0, 2, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_matcher_fzf_operators() {
+        assert_eq!(match_paths("'api !old"), vec!["api"]);
+        assert_eq!(match_paths("^svc-"), vec!["svc-auth"]);
+        assert_eq!(match_paths("old$"), vec!["api-old"]);
+        assert_eq!(match_paths("'web"), vec!["web/feature", "web/main"]);
+        assert_eq!(match_paths("web feat"), vec!["web/feature"]);
+    }
+
+    #[test]
+    fn test_matcher_basename_terms() {
+        assert!(match_paths("main").contains(&"mobile/app-info".to_string()));
+        assert_eq!(match_paths("/main"), vec!["maintenance", "web/main"]);
+        assert_eq!(match_paths("/^main$"), vec!["web/main"]);
+        assert_eq!(match_paths("/web"), Vec::<String>::new());
+        assert_eq!(match_paths("web !/main"), vec!["web/feature"]);
+    }
+
+    #[test]
+    fn test_split_query() {
+        assert_eq!(
+            split_query("api /main !/old x\\ y"),
+            (
+                vec!["api".to_string(), "x\\ y".to_string()],
+                vec!["main".to_string(), "!old".to_string()]
+            )
+        );
+    }
+
+    fn ranking_projects() -> Vec<Project> {
+        vec![
+            create_test_project(
+                "/p/apps/legacy/some-app-tools",
+                "apps/legacy/some-app-tools",
+            ),
+            create_test_project("/p/apps/app-core", "apps/app-core"),
+            create_test_project("/p/app", "app"),
+        ]
+    }
+
+    #[test]
+    fn test_ranking_prefers_exact_basename_then_prefix() {
+        let mut matcher = Matcher::new();
+        matcher.add_projects(ranking_projects());
+        let matches = matcher.find_scored("app");
+
+        let order: Vec<&str> = matches.iter().map(|m| m.project.display_path()).collect();
+        assert_eq!(
+            order,
+            vec!["app", "apps/app-core", "apps/legacy/some-app-tools"]
+        );
+
+        let top = matches[0].score;
+        assert_eq!(top.exact_basename, RankingConfig::default().exact_basename);
+        assert_eq!(top.basename_prefix, 0);
+        assert_eq!(
+            matches[1].score.basename_prefix,
+            RankingConfig::default().basename_prefix
+        );
+        assert_eq!(matches[2].score.depth, -20);
+    }
+
+    #[test]
+    fn test_ranking_is_configurable() {
+        let ranking = RankingConfig {
+            exact_basename: 0,
+            basename_prefix: 0,
+            depth_penalty: 0,
+            length_penalty: 0,
+            ..RankingConfig::default()
+        };
+        let mut matcher = Matcher::with_ranking(ranking);
+        matcher.add_projects(ranking_projects());
+        let matches = matcher.find_scored("'app");
+
+        // Equal fuzzy scores fall back to shallower, then shorter paths
+        assert!(matches.iter().all(|m| m.score.total() == m.score.fuzzy));
+        let order: Vec<&str> = matches.iter().map(|m| m.project.display_path()).collect();
+        assert_eq!(
+            order,
+            vec!["app", "apps/app-core", "apps/legacy/some-app-tools"]
+        );
+    }
+
+    #[test]
+    fn test_imported_history_changes_top_match() {
+        let top = |history: History| {
+            let mut matcher = Matcher::new().with_history(history);
+            matcher.add_projects(ranking_projects());
+            matcher.find_scored("apps").remove(0)
+        };
+        assert_eq!(
+            top(History::default()).project.display_path(),
+            "apps/app-core"
+        );
+
+        let mut history = History::default();
+        let entries = vec![history::ImportedEntry {
+            path: PathBuf::from("/p/apps/legacy/some-app-tools/src"),
+            rank: 50.0,
+            last_visit: history::now(),
+        }];
+        history::import(&mut history, &ranking_projects(), entries);
+
+        let best = top(history);
+        assert_eq!(best.project.display_path(), "apps/legacy/some-app-tools");
+        assert!(best.score.frecency > 0);
+    }
+
+    #[test]
+    fn test_auto_select() {
+        let ranking = RankingConfig::default();
+        let mut matcher = Matcher::new();
+        matcher.add_projects(ranking_projects());
+
+        // The only exact basename match wins
+        let matches = matcher.find_scored("app");
+        let best = auto_select(&matches, &ranking).unwrap();
+        assert_eq!(best.project.display_path(), "app");
+
+        // Disabled by config
+        let disabled = RankingConfig {
+            auto_select: false,
+            ..RankingConfig::default()
+        };
+        assert!(auto_select(&matches, &disabled).is_none());
+
+        // Close scores without an exact match leave the choice to the user
+        let matches = matcher.find_scored("ap");
+        assert!(matches.len() > 1);
+        assert!(auto_select(&matches, &ranking).is_none());
+
+        // With a smaller margin the lead is decisive
+        let generous = RankingConfig {
+            auto_select_margin: 1,
+            ..RankingConfig::default()
+        };
+        let best = auto_select(&matches, &generous).unwrap();
+        assert_eq!(best.project.display_path(), "app");
+    }
+
+    #[test]
+    fn test_auto_select_ambiguous_exact_matches() {
+        let mut matcher = Matcher::new();
+        matcher.add_projects(vec![
+            create_test_project("/p/api/main", "api/main"),
+            create_test_project("/p/web/main", "web/main"),
+        ]);
+        let matches = matcher.find_scored("/main");
+        assert!(matches.iter().all(|m| m.exact));
+        assert!(auto_select(&matches, &RankingConfig::default()).is_none());
+    }
 }
@@ -0,0 +1,473 @@
+use crate::config::Config;
+use crate::kind::detect_kind;
+use crate::scanner::Project;
+use crate::serve::{RpcError, Server, params, serve_json_rpc};
+use crate::tags::normalize_tag;
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// MCP protocol versions this server speaks, newest first
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Longest README summary returned, in characters
+const SUMMARY_LENGTH: usize = 500;
+
+/// A Model Context Protocol server over stdio, exposing the project catalog
+/// to coding agents as tools
+pub struct McpServer {
+    server: Server,
+}
+
+#[derive(Deserialize)]
+struct CallParams {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SearchArgs {
+    query: String,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ListArgs {
+    kind: Option<String>,
+    tag: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PathArgs {
+    path: PathBuf,
+}
+
+/// The tools advertised by `tools/list`
+fn tools() -> Value {
+    let path_schema = json!({
+        "type": "object",
+        "properties": {
+            "path": { "type": "string", "description": "Absolute path of a file or directory" },
+        },
+        "required": ["path"],
+    });
+
+    json!([
+        {
+            "name": "search_projects",
+            "description": "Fuzzy-search the local project catalog, best match first. \
+                Uses pj's query syntax: space-separated terms, fzf operators ('exact, ^prefix, suffix$, !not), \
+                /name to match only the directory name and #tag to filter by tag.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "Search terms, e.g. \"api\"" },
+                    "limit": { "type": "integer", "description": "Maximum number of results (default 10)" },
+                },
+                "required": ["query"],
+            },
+        },
+        {
+            "name": "list_projects",
+            "description": "List every local project with its kind (rust, node, ...) and tags",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "kind": { "type": "string", "description": "Only projects of this kind" },
+                    "tag": { "type": "string", "description": "Only projects with this tag" },
+                },
+            },
+        },
+        {
+            "name": "project_info",
+            "description": "Details of the project containing a path: kind, tags, a README summary and version control status",
+            "inputSchema": path_schema,
+        },
+        {
+            "name": "resolve_path",
+            "description": "Find the root of the project containing a file or directory",
+            "inputSchema": path_schema,
+        },
+    ])
+}
+
+impl McpServer {
+    /// Scan and watch `config`'s scan paths, using the pj state directory
+    pub fn open(config: Config) -> Result<Self> {
+        Ok(Self {
+            server: Server::open(config)?,
+        })
+    }
+
+    /// Like [`McpServer::open`], keeping history and tags in `state_dir`
+    pub fn new(config: Config, state_dir: PathBuf) -> Result<Self> {
+        Ok(Self {
+            server: Server::new(config, state_dir)?,
+        })
+    }
+
+    /// Answer requests on stdin until it closes
+    pub fn serve_stdio(&mut self) -> Result<()> {
+        let stdin = std::io::stdin().lock();
+        let stdout = std::io::stdout().lock();
+        serve_json_rpc(stdin, stdout, |method, params| self.handle(method, params))
+    }
+
+    /// Answer one request or notification
+    pub fn handle(&mut self, method: &str, params_value: Value) -> Result<Value, RpcError> {
+        match method {
+            "initialize" => {
+                let requested = params_value["protocolVersion"].as_str();
+                let version = PROTOCOL_VERSIONS
+                    .iter()
+                    .find(|v| Some(**v) == requested)
+                    .unwrap_or(&PROTOCOL_VERSIONS[0]);
+                Ok(json!({
+                    "protocolVersion": version,
+                    "capabilities": { "tools": {} },
+                    "serverInfo": { "name": "pj", "version": env!("CARGO_PKG_VERSION") },
+                }))
+            }
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tools() })),
+            "tools/call" => {
+                let CallParams { name, arguments } = params(params_value)?;
+                if !tools()
+                    .as_array()
+                    .is_some_and(|tools| tools.iter().any(|t| t["name"] == name.as_str()))
+                {
+                    return Err(RpcError::new(
+                        RpcError::INVALID_PARAMS,
+                        format!("Unknown tool: {}", name),
+                    ));
+                }
+
+                // Failures are reported to the model as tool results
+                Ok(match self.call_tool(&name, arguments) {
+                    Ok(result) => json!({
+                        "content": [{ "type": "text", "text": serde_json::to_string_pretty(&result).unwrap_or_default() }],
+                        "structuredContent": { "result": result },
+                    }),
+                    Err(err) => json!({
+                        "content": [{ "type": "text", "text": format!("{:#}", err) }],
+                        "isError": true,
+                    }),
+                })
+            }
+            method if method.starts_with("notifications/") => Ok(Value::Null),
+            _ => Err(RpcError::method_not_found(method)),
+        }
+    }
+
+    fn call_tool(&mut self, name: &str, arguments: Value) -> Result<Value> {
+        let arguments = if arguments.is_null() {
+            json!({})
+        } else {
+            arguments
+        };
+
+        match name {
+            "search_projects" => {
+                let args: SearchArgs = serde_json::from_value(arguments)?;
+                let matches = self.server.search(&args.query)?;
+                Ok(matches
+                    .into_iter()
+                    .take(args.limit.unwrap_or(10))
+                    .map(|m| {
+                        json!({
+                            "path": m.project.path,
+                            "display_path": m.project.display_path,
+                            "score": m.score.total(),
+                        })
+                    })
+                    .collect())
+            }
+            "list_projects" => {
+                let args: ListArgs = serde_json::from_value(arguments)?;
+                let tags = self.server.tags()?;
+                let projects = self.server.projects()?;
+
+                Ok(projects
+                    .iter()
+                    .map(|project| {
+                        let kind = detect_kind(&project.path);
+                        let project_tags = tags.tags_for(&project.path);
+                        (project, kind, project_tags)
+                    })
+                    .filter(|(_, kind, _)| args.kind.is_none() || *kind == args.kind.as_deref())
+                    .filter(|(_, _, project_tags)| {
+                        args.tag
+                            .as_deref()
+                            .is_none_or(|tag| project_tags.contains(&normalize_tag(tag)))
+                    })
+                    .map(|(project, kind, project_tags)| {
+                        json!({
+                            "path": project.path,
+                            "display_path": project.display_path,
+                            "kind": kind,
+                            "tags": project_tags,
+                        })
+                    })
+                    .collect())
+            }
+            "project_info" => {
+                let args: PathArgs = serde_json::from_value(arguments)?;
+                let project = self.project_for(&args.path)?;
+                let tags = self.server.tags()?.tags_for(&project.path);
+
+                Ok(json!({
+                    "path": project.path,
+                    "display_path": project.display_path,
+                    "kind": detect_kind(&project.path),
+                    "tags": tags,
+                    "readme": readme_summary(&project.path),
+                    "vcs": vcs_status(&project.path),
+                }))
+            }
+            "resolve_path" => {
+                let args: PathArgs = serde_json::from_value(arguments)?;
+                let project = self.project_for(&args.path)?;
+                Ok(json!({ "path": project.path, "display_path": project.display_path }))
+            }
+            _ => unreachable!("checked against tools()"),
+        }
+    }
+
+    fn project_for(&mut self, path: &Path) -> Result<Project> {
+        self.server
+            .resolve(path)?
+            .ok_or_else(|| anyhow!("{} is not inside a known project", path.display()))
+    }
+}
+
+/// The first paragraph of the project's README, skipping headings, badges
+/// and HTML, shortened to 500 characters
+pub fn readme_summary(project: &Path) -> Option<String> {
+    let readme = fs::read_dir(project)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_stem()
+                .is_some_and(|stem| stem.eq_ignore_ascii_case("readme"))
+        })
+        .min()?;
+    let contents = fs::read_to_string(readme).ok()?;
+
+    let paragraph: Vec<&str> = contents
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| {
+            line.is_empty()
+                || line.starts_with('#')
+                || line.starts_with('=')
+                || line.starts_with("[![")
+                || line.starts_with("![")
+                || line.starts_with('<')
+        })
+        .take_while(|line| !line.is_empty())
+        .collect();
+    if paragraph.is_empty() {
+        return None;
+    }
+
+    let summary = paragraph.join(" ");
+    Some(match summary.char_indices().nth(SUMMARY_LENGTH) {
+        Some((end, _)) => format!("{}...", &summary[..end]),
+        None => summary,
+    })
+}
+
+/// The version control state of a project: for git the branch, upstream
+/// tracking and changed files; for jj the working-copy change. `None` if the
+/// project has neither or the tool isn't installed.
+pub fn vcs_status(project: &Path) -> Option<Value> {
+    if project.join(".jj").exists() {
+        let output = run(
+            Command::new("jj")
+                .arg("-R")
+                .arg(project)
+                .args(["log", "--no-graph", "-r", "@", "-T"])
+                .arg(r#"change_id.short() ++ "\n" ++ description.first_line() ++ "\n" ++ if(empty, "clean", "modified")"#),
+        )
+        .ok()?;
+        let mut lines = output.lines();
+        return Some(json!({
+            "vcs": "jj",
+            "change": lines.next(),
+            "description": lines.next(),
+            "working_copy": lines.next(),
+        }));
+    }
+
+    if project.join(".git").exists() {
+        let output = run(Command::new("git").arg("-C").arg(project).args([
+            "status",
+            "--porcelain=v1",
+            "--branch",
+        ]))
+        .ok()?;
+        return Some(parse_git_status(&output));
+    }
+
+    None
+}
+
+fn run(command: &mut Command) -> Result<String> {
+    let output = command.output().context("Failed to run version control")?;
+    if !output.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Parse `git status --porcelain=v1 --branch`: a `## branch...upstream
+/// [ahead 1, behind 2]` header and one line per changed file
+fn parse_git_status(output: &str) -> Value {
+    let mut lines = output.lines();
+    let header = lines
+        .next()
+        .and_then(|line| line.strip_prefix("## "))
+        .unwrap_or_default();
+
+    let (branches, tracking) = match header.split_once(" [") {
+        Some((branches, tracking)) => (branches, tracking.trim_end_matches(']')),
+        None => (header, ""),
+    };
+    let (branch, upstream) = match branches.split_once("...") {
+        Some((branch, upstream)) => (branch, Some(upstream)),
+        None => (branches, None),
+    };
+    let branch = branch.strip_prefix("No commits yet on ").unwrap_or(branch);
+    let count = |name: &str| -> u64 {
+        tracking
+            .split(", ")
+            .find_map(|part| part.strip_prefix(name)?.trim().parse().ok())
+            .unwrap_or(0)
+    };
+
+    let mut status = Map::new();
+    status.insert("vcs".to_string(), json!("git"));
+    status.insert("branch".to_string(), json!(branch));
+    status.insert("upstream".to_string(), json!(upstream));
+    status.insert("ahead".to_string(), json!(count("ahead")));
+    status.insert("behind".to_string(), json!(count("behind")));
+    status.insert("changed_files".to_string(), json!(lines.count()));
+    Value::Object(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn run_session(server: &mut McpServer, requests: &[Value]) -> Vec<Value> {
+        let input: String = requests.iter().map(|r| format!("{}\n", r)).collect();
+        let mut output = Vec::new();
+        serve_json_rpc(input.as_bytes(), &mut output, |method, params| {
+            server.handle(method, params)
+        })
+        .unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn call(id: u64, name: &str, arguments: Value) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": name, "arguments": arguments },
+        })
+    }
+
+    #[test]
+    fn test_mcp_session() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("projects");
+        fs::create_dir_all(root.join("api/.git")).unwrap();
+        fs::create_dir_all(root.join("web/.git")).unwrap();
+        fs::write(root.join("api/Cargo.toml"), "").unwrap();
+        fs::write(
+            root.join("api/README.md"),
+            "# api\n\n[![ci](x)](y)\n\nThe billing API.\nServes invoices.\n\n## Usage\n",
+        )
+        .unwrap();
+        let config = Config {
+            scan_paths: vec![root.as_path().into()],
+            ..Config::default()
+        };
+        let mut server = McpServer::new(config, temp_dir.path().to_path_buf()).unwrap();
+
+        let responses = run_session(
+            &mut server,
+            &[
+                json!({"jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {"protocolVersion": "2025-03-26"}}),
+                json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+                json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}),
+                call(2, "search_projects", json!({"query": "api"})),
+                call(3, "list_projects", json!({"kind": "rust"})),
+                call(
+                    4,
+                    "project_info",
+                    json!({"path": root.join("api/src/lib.rs")}),
+                ),
+                call(5, "resolve_path", json!({"path": "/"})),
+                call(6, "nope", json!({})),
+            ],
+        );
+
+        assert_eq!(responses.len(), 7);
+        assert_eq!(responses[0]["result"]["protocolVersion"], "2025-03-26");
+        assert_eq!(responses[1]["result"]["tools"].as_array().unwrap().len(), 4);
+
+        let found = &responses[2]["result"]["structuredContent"]["result"];
+        assert_eq!(found.as_array().unwrap().len(), 1);
+        assert_eq!(found[0]["display_path"], "api");
+
+        let listed = &responses[3]["result"]["structuredContent"]["result"];
+        assert_eq!(
+            listed,
+            &json!([{
+                "path": root.join("api"),
+                "display_path": "api",
+                "kind": "rust",
+                "tags": [],
+            }])
+        );
+
+        let info = &responses[4]["result"]["structuredContent"]["result"];
+        assert_eq!(info["display_path"], "api");
+        assert_eq!(info["readme"], "The billing API. Serves invoices.");
+
+        assert_eq!(responses[5]["result"]["isError"], true);
+        assert_eq!(responses[6]["error"]["code"], RpcError::INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_parse_git_status() {
+        let status = parse_git_status(
+            "## main...origin/main [ahead 2, behind 1]\n M src/lib.rs\n?? notes.txt\n",
+        );
+        assert_eq!(status["branch"], "main");
+        assert_eq!(status["upstream"], "origin/main");
+        assert_eq!(status["ahead"], 2);
+        assert_eq!(status["behind"], 1);
+        assert_eq!(status["changed_files"], 2);
+
+        let status = parse_git_status("## No commits yet on main\n");
+        assert_eq!(status["branch"], "main");
+        assert_eq!(status["upstream"], Value::Null);
+        assert_eq!(status["changed_files"], 0);
+    }
+}
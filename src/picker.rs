@@ -1,7 +1,8 @@
 use crate::scanner::Project;
-use anyhow::Result;
 use nucleo_picker::{Picker, render::StrRenderer};
+use std::io;
 
+/// A full-screen fuzzy picker over projects, drawn on the terminal
 pub struct InteractivePicker {
     projects: Vec<Project>,
 }
@@ -11,8 +12,9 @@ impl InteractivePicker {
         Self { projects }
     }
 
-    /// Show interactive picker and return selected project
-    pub fn pick(&self) -> Result<Option<Project>> {
+    /// Show interactive picker and return selected project, or `None` if the
+    /// user cancelled
+    pub fn pick(&self) -> io::Result<Option<Project>> {
         if self.projects.is_empty() {
             return Ok(None);
         }
@@ -0,0 +1,48 @@
+use crate::hooks::Hooks;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Per-project settings file, looked for in each project root
+pub const PROJECT_FILE: &str = ".pj.toml";
+
+/// The parts of a `.pj.toml` that pj reads
+#[derive(Debug, Default, Deserialize)]
+pub struct ProjectFile {
+    #[serde(default)]
+    pub hooks: Hooks,
+
+    /// Tags that apply to the project wherever it is checked out
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl ProjectFile {
+    /// Read the `.pj.toml` in `project`, returning its path and parsed form,
+    /// or `None` if there isn't one
+    pub fn load(project: &Path) -> Result<Option<(PathBuf, Self)>> {
+        let file = project.join(PROJECT_FILE);
+        let contents = match fs::read_to_string(&file) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read {}", file.display()));
+            }
+        };
+
+        let parsed = Self::parse(&file, &contents)?;
+        Ok(Some((file, parsed)))
+    }
+
+    /// Read a project file that must exist
+    pub fn read(file: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(file)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        Self::parse(file, &contents)
+    }
+
+    fn parse(file: &Path, contents: &str) -> Result<Self> {
+        toml::from_str(contents).with_context(|| format!("Failed to parse {}", file.display()))
+    }
+}
@@ -0,0 +1,145 @@
+use crate::config::{Config, ScanPath};
+use crate::scanner::Project;
+use std::path::{Path, PathBuf};
+
+/// The project containing `path`: the deepest project root that is `path` or
+/// one of its ancestors
+pub fn enclosing_project<'a>(projects: &'a [Project], path: &Path) -> Option<&'a Project> {
+    projects
+        .iter()
+        .filter(|project| path.starts_with(&project.path))
+        .max_by_key(|project| project.path.components().count())
+}
+
+/// A project root at or above a path, found by [`enclosing_projects`]
+#[derive(Debug, Clone)]
+pub struct EnclosingProject {
+    /// The root, with its display path relative to `scan_root`, or the
+    /// whole path if no scan path contains it
+    pub project: Project,
+    /// The most specific scan path containing the root
+    pub scan_root: Option<PathBuf>,
+    /// Configured markers present in the root
+    pub markers: Vec<String>,
+}
+
+/// Every directory at or above `path` that contains one of the configured
+/// project markers, innermost first. Unlike [`enclosing_project`] this
+/// ignores the scanned projects, scan paths, depth and excludes, so it also
+/// finds projects pj does not list. `path` is canonicalized first, so
+/// symlinks and `..` resolve to the directories they lead to.
+pub fn enclosing_projects(config: &Config, path: &Path) -> Vec<EnclosingProject> {
+    canonicalize_existing(path)
+        .ancestors()
+        .filter_map(|dir| {
+            let markers: Vec<String> = config
+                .project_markers
+                .iter()
+                .filter(|marker| dir.join(marker).exists())
+                .cloned()
+                .collect();
+            if markers.is_empty() {
+                return None;
+            }
+
+            let scan_root = enclosing_scan_root(config, dir).map(|(_, root)| root);
+            let project = match &scan_root {
+                Some(root) => Project::new(dir.to_path_buf(), root),
+                None => Project::new(dir.to_path_buf(), Path::new("")),
+            };
+            Some(EnclosingProject {
+                project,
+                scan_root,
+                markers,
+            })
+        })
+        .collect()
+}
+
+/// Canonicalize `path`, or if it doesn't exist, its deepest existing
+/// ancestor with the rest of the path appended
+fn canonicalize_existing(path: &Path) -> PathBuf {
+    for ancestor in path.ancestors() {
+        if let Ok(canonical) = ancestor.canonicalize() {
+            let rest = path.strip_prefix(ancestor).unwrap_or(Path::new(""));
+            return if rest.as_os_str().is_empty() {
+                canonical
+            } else {
+                canonical.join(rest)
+            };
+        }
+    }
+    path.to_path_buf()
+}
+
+/// Find the most specific scan path containing `path`, with `~` expanded.
+/// A canonical `path` also matches the canonical form of a symlinked scan
+/// path, which is then returned in that form.
+pub fn enclosing_scan_root<'a>(config: &'a Config, path: &Path) -> Option<(&'a ScanPath, PathBuf)> {
+    config
+        .scan_paths
+        .iter()
+        .filter_map(|scan_path| {
+            let root = scan_path.expanded();
+            if path.starts_with(&root) {
+                return Some((scan_path, root));
+            }
+            let canonical = root.canonicalize().ok()?;
+            path.starts_with(&canonical)
+                .then_some((scan_path, canonical))
+        })
+        .max_by_key(|(_, root)| root.components().count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_enclosing_projects_innermost_first() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("projects");
+        fs::create_dir_all(root.join("web/.git")).unwrap();
+        fs::create_dir_all(root.join("web/vendor/lib/.jj")).unwrap();
+        fs::create_dir_all(root.join("web/vendor/lib/src")).unwrap();
+
+        let config = Config {
+            scan_paths: vec![root.as_path().into()],
+            project_markers: vec![".git".to_string(), ".jj".to_string()],
+            ..Config::default()
+        };
+        let chain = enclosing_projects(&config, &root.join("web/vendor/lib/src"));
+
+        let display: Vec<&str> = chain.iter().map(|e| e.project.display_path()).collect();
+        assert_eq!(display, vec!["web/vendor/lib", "web"]);
+        assert_eq!(chain[0].markers, vec![".jj".to_string()]);
+        assert_eq!(chain[0].scan_root, Some(root.clone()));
+
+        assert!(enclosing_projects(&config, &root).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_enclosing_projects_canonicalizes() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("projects");
+        fs::create_dir_all(root.join("web/.git")).unwrap();
+        std::os::unix::fs::symlink(root.join("web"), temp_dir.path().join("link")).unwrap();
+
+        let config = Config {
+            scan_paths: vec![root.as_path().into()],
+            ..Config::default()
+        };
+        let canonical = root.canonicalize().unwrap().join("web");
+
+        let chain = enclosing_projects(&config, &temp_dir.path().join("link/missing/file.rs"));
+        assert_eq!(chain.len(), 1);
+        assert_eq!(chain[0].project.path, canonical);
+        assert_eq!(chain[0].project.display_path(), "web");
+
+        let chain = enclosing_projects(&config, &root.join("web/missing/../.git"));
+        assert_eq!(chain[0].project.path, canonical);
+    }
+}
@@ -1,6 +1,12 @@
-use crate::config::Config;
-use anyhow::Result;
+use crate::config::{Config, ScanPath};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
 use walkdir::WalkDir;
 
 #[derive(Debug, Clone)]
@@ -27,41 +33,564 @@ impl Project {
     }
 }
 
-/// Check if a directory is a project based on the markers
-fn is_project(dir: &Path, markers: &[String]) -> bool {
-    markers.iter().any(|marker| dir.join(marker).exists())
+/// Counters collected while walking a single scan path
+#[derive(Debug, Clone, Default)]
+pub struct ScanStats {
+    /// The scan path, with `~` expanded
+    pub root: PathBuf,
+    /// Whether the scan path existed
+    pub exists: bool,
+    /// Directories visited by the walk
+    pub dirs_visited: usize,
+    /// Filesystem metadata lookups made to detect directories and markers
+    pub stat_calls: usize,
+    /// Project roots found
+    pub projects_found: usize,
+    /// Entries that could not be read due to permissions
+    pub permission_errors: usize,
+    /// Symlinks whose target does not exist
+    pub broken_symlinks: usize,
+    /// Any other walk errors
+    pub other_errors: usize,
+    /// Wall time spent walking this scan path
+    pub elapsed: Duration,
 }
 
-/// Scan directories for project roots only
-pub fn scan_projects(config: &Config) -> Result<Vec<Project>> {
-    let mut projects = Vec::new();
+/// What went wrong at a path while scanning
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanDiagnosticKind {
+    /// A configured scan path does not exist
+    MissingScanPath,
+    /// A directory could not be read due to permissions
+    PermissionDenied,
+    /// A symlink points back at one of its own ancestors
+    SymlinkLoop,
+    /// Any other error reading a directory entry
+    Unreadable(String),
+    /// A scan path lies inside another configured scan path
+    OverlappingScanPath { parent: PathBuf },
+}
 
-    for scan_path in &config.scan_paths {
-        // Expand tilde in path
-        let scan_path = shellexpand::tilde(&scan_path.to_string_lossy()).to_string();
-        let scan_path = PathBuf::from(scan_path);
+impl ScanDiagnosticKind {
+    /// Whether this is only a warning: the projects found are unaffected, so
+    /// `strict` mode doesn't fail because of it
+    pub fn is_warning(&self) -> bool {
+        matches!(self, ScanDiagnosticKind::OverlappingScanPath { .. })
+    }
+}
+
+/// A problem encountered while scanning, which did not stop the scan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanDiagnostic {
+    pub path: PathBuf,
+    pub kind: ScanDiagnosticKind,
+}
+
+impl fmt::Display for ScanDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path.display();
+        match &self.kind {
+            ScanDiagnosticKind::MissingScanPath => write!(f, "Scan path does not exist: {}", path),
+            ScanDiagnosticKind::PermissionDenied => write!(f, "Permission denied: {}", path),
+            ScanDiagnosticKind::SymlinkLoop => write!(f, "Symlink loop: {}", path),
+            ScanDiagnosticKind::Unreadable(err) => write!(f, "Unreadable: {}: {}", path, err),
+            ScanDiagnosticKind::OverlappingScanPath { parent } => write!(
+                f,
+                "Scan path {} is inside scan path {}",
+                path,
+                parent.display()
+            ),
+        }
+    }
+}
+
+/// An error that stops a scan
+#[derive(Debug, thiserror::Error)]
+pub enum ScanError {
+    /// An `exclude` entry is not a valid glob
+    #[error("Invalid exclude pattern {pattern:?}: {source}")]
+    InvalidExclude {
+        pattern: String,
+        #[source]
+        source: glob::PatternError,
+    },
+    /// A problem with one scan path or directory, yielded by [`ScanIter`]
+    /// without stopping the scan
+    #[error("{0}")]
+    Diagnostic(ScanDiagnostic),
+    /// `strict` is on and the scan hit problems
+    #[error("Scan failed in strict mode with {} error(s):\n  {}", .0.len(), join_diagnostics(.0))]
+    Strict(Vec<ScanDiagnostic>),
+}
+
+impl ScanError {
+    /// Whether this is a [`ScanError::Diagnostic`] that is only a warning
+    pub fn is_warning(&self) -> bool {
+        matches!(self, ScanError::Diagnostic(diagnostic) if diagnostic.kind.is_warning())
+    }
+}
+
+fn join_diagnostics(diagnostics: &[ScanDiagnostic]) -> String {
+    let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+    messages.join("\n  ")
+}
+
+/// A compiled `exclude` entry. Patterns containing `/` match the whole
+/// directory path (after `~` expansion); others match the directory name.
+#[derive(Debug, Clone)]
+pub struct Exclude {
+    pub pattern: String,
+    glob: glob::Pattern,
+    whole_path: bool,
+}
+
+impl Exclude {
+    pub fn new(pattern: &str) -> Result<Self, ScanError> {
+        let whole_path = pattern.contains('/');
+        let expanded = if whole_path {
+            shellexpand::tilde(pattern).to_string()
+        } else {
+            pattern.to_string()
+        };
+
+        let glob = glob::Pattern::new(&expanded).map_err(|source| ScanError::InvalidExclude {
+            pattern: pattern.to_string(),
+            source,
+        })?;
+
+        Ok(Self {
+            pattern: pattern.to_string(),
+            glob,
+            whole_path,
+        })
+    }
+
+    pub fn matches(&self, dir: &Path) -> bool {
+        if self.whole_path {
+            self.glob.matches_path(dir)
+        } else {
+            dir.file_name()
+                .is_some_and(|name| self.glob.matches(&name.to_string_lossy()))
+        }
+    }
+}
 
-        if !scan_path.exists() {
-            eprintln!("Warning: Scan path does not exist: {}", scan_path.display());
+/// Compile the config's `exclude` entries
+pub fn excludes(config: &Config) -> Result<Vec<Exclude>, ScanError> {
+    config.exclude.iter().map(|p| Exclude::new(p)).collect()
+}
+
+/// The result of a scan: projects plus per-scan-path statistics and any
+/// problems encountered along the way
+#[derive(Debug, Clone, Default)]
+pub struct ScanReport {
+    pub projects: Vec<Project>,
+    pub stats: Vec<ScanStats>,
+    pub diagnostics: Vec<ScanDiagnostic>,
+}
+
+/// Check if a directory is a project based on the markers, adding the number
+/// of marker lookups made to `stat_calls`
+fn is_project(dir: &Path, markers: &[String], stat_calls: &mut usize) -> bool {
+    markers.iter().any(|marker| {
+        *stat_calls += 1;
+        dir.join(marker).exists()
+    })
+}
+
+/// Projects found so far, keyed by canonical path, with their index in
+/// `ScanReport::projects` and the specificity of the scan path they came from
+type Seen = HashMap<PathBuf, (usize, usize)>;
+
+/// Specificity of a scan path: the number of components of its canonical form
+fn specificity(scan_path: &Path) -> usize {
+    scan_path
+        .canonicalize()
+        .unwrap_or_else(|_| scan_path.to_path_buf())
+        .components()
+        .count()
+}
+
+/// Classify a walk error, or `None` for a dangling symlink
+fn walk_diagnostic(err: &walkdir::Error, scan_path: &Path) -> Option<ScanDiagnostic> {
+    let io_kind = err.io_error().map(|e| e.kind());
+    if io_kind == Some(ErrorKind::NotFound) {
+        return None;
+    }
+
+    let kind = if err.loop_ancestor().is_some() {
+        ScanDiagnosticKind::SymlinkLoop
+    } else if io_kind == Some(ErrorKind::PermissionDenied) {
+        ScanDiagnosticKind::PermissionDenied
+    } else {
+        ScanDiagnosticKind::Unreadable(err.to_string())
+    };
+
+    Some(ScanDiagnostic {
+        path: err.path().unwrap_or(scan_path).to_path_buf(),
+        kind,
+    })
+}
+
+/// Walk a single scan path, appending found projects and diagnostics to
+/// `report`. A project reachable through several symlinks or overlapping
+/// scan paths is listed once, relative to the most specific scan path.
+fn scan_root(
+    scan_path: &ScanPath,
+    config: &Config,
+    excludes: &[Exclude],
+    seen: &mut Seen,
+    report: &mut ScanReport,
+) -> ScanStats {
+    let start = Instant::now();
+    let follow_links = config.follows_symlinks(scan_path);
+    let scan_path = &scan_path.expanded();
+    let root_specificity = specificity(scan_path);
+    let mut stats = ScanStats {
+        root: scan_path.to_path_buf(),
+        exists: scan_path.exists(),
+        ..ScanStats::default()
+    };
+
+    if !stats.exists {
+        report.diagnostics.push(ScanDiagnostic {
+            path: scan_path.to_path_buf(),
+            kind: ScanDiagnosticKind::MissingScanPath,
+        });
+        return stats;
+    }
+
+    // Find project roots (directories containing project markers)
+    for entry in WalkDir::new(scan_path)
+        .max_depth(config.max_depth)
+        .follow_links(follow_links)
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0 || !excludes.iter().any(|e| e.matches(entry.path()))
+        })
+    {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                let Some(diagnostic) = walk_diagnostic(&err, scan_path) else {
+                    // A dangling symlink, reported as an error when following links
+                    stats.broken_symlinks += 1;
+                    continue;
+                };
+
+                match diagnostic.kind {
+                    ScanDiagnosticKind::PermissionDenied => stats.permission_errors += 1,
+                    _ => stats.other_errors += 1,
+                }
+                report.diagnostics.push(diagnostic);
+                continue;
+            }
+        };
+        let path = entry.path();
+
+        stats.stat_calls += 1;
+        if !path.is_dir() {
+            if entry.path_is_symlink() && !path.exists() {
+                stats.broken_symlinks += 1;
+            }
             continue;
         }
+        stats.dirs_visited += 1;
+
+        if is_project(path, &config.project_markers, &mut stats.stat_calls) {
+            let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+            let project = Project::new(path.to_path_buf(), scan_path);
+
+            match seen.get_mut(&canonical) {
+                Some((index, found_specificity)) => {
+                    if root_specificity > *found_specificity {
+                        report.projects[*index] = project;
+                        *found_specificity = root_specificity;
+                    }
+                }
+                None => {
+                    seen.insert(canonical, (report.projects.len(), root_specificity));
+                    report.projects.push(project);
+                    stats.projects_found += 1;
+                }
+            }
+        }
+    }
+
+    stats.elapsed = start.elapsed();
+    stats
+}
+
+/// Pairs of configured scan paths where the first lies inside the second
+pub fn overlapping_scan_paths(config: &Config) -> Vec<(PathBuf, PathBuf)> {
+    let roots: Vec<(PathBuf, PathBuf)> = config
+        .scan_paths
+        .iter()
+        .map(|scan_path| {
+            let expanded = scan_path.expanded();
+            let canonical = expanded.canonicalize().unwrap_or_else(|_| expanded.clone());
+            (expanded, canonical)
+        })
+        .collect();
+
+    let mut overlaps = Vec::new();
+    for (child, child_canonical) in &roots {
+        for (parent, parent_canonical) in &roots {
+            if child_canonical != parent_canonical && child_canonical.starts_with(parent_canonical)
+            {
+                overlaps.push((child.clone(), parent.clone()));
+            }
+        }
+    }
+
+    overlaps
+}
+
+/// Warnings for scan paths nested inside other scan paths
+fn overlap_diagnostics(overlaps: &[(PathBuf, PathBuf)]) -> Vec<ScanDiagnostic> {
+    overlaps
+        .iter()
+        .map(|(child, parent)| ScanDiagnostic {
+            path: child.clone(),
+            kind: ScanDiagnosticKind::OverlappingScanPath {
+                parent: parent.clone(),
+            },
+        })
+        .collect()
+}
+
+/// Scan directories for project roots, collecting statistics for each scan
+/// path. In `strict` mode any diagnostic other than a warning makes the scan
+/// fail.
+pub fn scan(config: &Config) -> Result<ScanReport, ScanError> {
+    let excludes = excludes(config)?;
+    let mut report = ScanReport::default();
+    let mut seen = Seen::new();
+
+    report.diagnostics = overlap_diagnostics(&overlapping_scan_paths(config));
+
+    for scan_path in &config.scan_paths {
+        let stats = scan_root(scan_path, config, &excludes, &mut seen, &mut report);
+        report.stats.push(stats);
+    }
+
+    if config.strict {
+        let errors: Vec<ScanDiagnostic> = report
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| !diagnostic.kind.is_warning())
+            .cloned()
+            .collect();
+        if !errors.is_empty() {
+            return Err(ScanError::Strict(errors));
+        }
+    }
+
+    Ok(report)
+}
+
+/// Scan directories for project roots only
+pub fn scan_projects(config: &Config) -> Result<Vec<Project>, ScanError> {
+    Ok(scan(config)?.projects)
+}
+
+/// Stops a [`ScanIter`] or [`scan_channel`] from another thread
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+type Walk = Box<dyn Iterator<Item = walkdir::Result<walkdir::DirEntry>> + Send>;
+
+/// A lazy scan that yields projects as the walk finds them, created by
+/// [`scan_iter`].
+///
+/// Problems that [`scan`] would collect as diagnostics are yielded as
+/// [`ScanError::Diagnostic`] items and the walk carries on; stop at the first
+/// `Err` that isn't [`ScanError::is_warning`] for `strict` behaviour.
+/// Overlapping scan paths are reported first, as warnings.
+///
+/// Unlike [`scan`], a scan path nested inside another is skipped by the outer
+/// walk rather than deduplicated afterwards, so each project is still listed
+/// relative to the most specific scan path.
+pub struct ScanIter {
+    markers: Vec<String>,
+    max_depth: usize,
+    /// Expanded scan paths still to walk, with whether to follow symlinks
+    roots: std::vec::IntoIter<(PathBuf, bool)>,
+    overlaps: Vec<(PathBuf, PathBuf)>,
+    /// Overlap warnings not yet yielded
+    warnings: std::vec::IntoIter<ScanDiagnostic>,
+    excludes: Arc<Vec<Exclude>>,
+    walk: Option<(Walk, PathBuf)>,
+    seen: HashSet<PathBuf>,
+    error: Option<ScanError>,
+    cancel: CancelToken,
+    limit: Option<usize>,
+    yielded: usize,
+}
+
+/// Start a lazy scan of `config`'s scan paths
+pub fn scan_iter(config: &Config) -> ScanIter {
+    let (excludes, error) = match excludes(config) {
+        Ok(excludes) => (excludes, None),
+        Err(err) => (Vec::new(), Some(err)),
+    };
+    let roots: Vec<(PathBuf, bool)> = config
+        .scan_paths
+        .iter()
+        .map(|scan_path| (scan_path.expanded(), config.follows_symlinks(scan_path)))
+        .collect();
+    let overlaps = overlapping_scan_paths(config);
+    let warnings = overlap_diagnostics(&overlaps);
+
+    ScanIter {
+        markers: config.project_markers.clone(),
+        max_depth: config.max_depth,
+        roots: roots.into_iter(),
+        overlaps,
+        warnings: warnings.into_iter(),
+        excludes: Arc::new(excludes),
+        walk: None,
+        seen: HashSet::new(),
+        error,
+        cancel: CancelToken::new(),
+        limit: None,
+        yielded: 0,
+    }
+}
+
+impl ScanIter {
+    /// Stop after yielding `limit` projects
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Stop as soon as `cancel` is cancelled
+    pub fn cancel_on(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Start walking `root`, skipping excluded directories and scan paths
+    /// nested inside it, which are walked on their own. Nested scan paths
+    /// are compared canonically, as in [`overlapping_scan_paths`], so one
+    /// configured through a symlink is still skipped.
+    fn start_walk(&self, root: &Path, follow_links: bool) -> Walk {
+        let excludes = Arc::clone(&self.excludes);
+        let canonical_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let nested: Vec<PathBuf> = self
+            .overlaps
+            .iter()
+            .filter(|(_, parent)| parent == root)
+            .filter_map(|(child, _)| {
+                let canonical = child.canonicalize().unwrap_or_else(|_| child.clone());
+                // Where the walk reaches the nested scan path below `root`
+                Some(root.join(canonical.strip_prefix(&canonical_root).ok()?))
+            })
+            .collect();
+
+        Box::new(
+            WalkDir::new(root)
+                .max_depth(self.max_depth)
+                .follow_links(follow_links)
+                .into_iter()
+                .filter_entry(move |entry| {
+                    entry.depth() == 0
+                        || !(excludes.iter().any(|e| e.matches(entry.path()))
+                            || nested.iter().any(|n| n == entry.path()))
+                }),
+        )
+    }
+}
+
+impl Iterator for ScanIter {
+    type Item = Result<Project, ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(err) = self.error.take() {
+            self.limit = Some(0);
+            return Some(Err(err));
+        }
+        if let Some(warning) = self.warnings.next() {
+            return Some(Err(ScanError::Diagnostic(warning)));
+        }
+
+        loop {
+            if self.cancel.is_cancelled() || self.limit.is_some_and(|l| self.yielded >= l) {
+                return None;
+            }
+
+            let Some((walk, root)) = &mut self.walk else {
+                let (root, follow_links) = self.roots.next()?;
+                if !root.exists() {
+                    return Some(Err(ScanError::Diagnostic(ScanDiagnostic {
+                        path: root,
+                        kind: ScanDiagnosticKind::MissingScanPath,
+                    })));
+                }
+                self.walk = Some((self.start_walk(&root, follow_links), root));
+                continue;
+            };
+
+            let entry = match walk.next() {
+                None => {
+                    self.walk = None;
+                    continue;
+                }
+                Some(Ok(entry)) => entry,
+                Some(Err(err)) => match walk_diagnostic(&err, root) {
+                    Some(diagnostic) => return Some(Err(ScanError::Diagnostic(diagnostic))),
+                    None => continue,
+                },
+            };
 
-        // Find project roots (directories containing project markers)
-        for entry in WalkDir::new(&scan_path)
-            .max_depth(config.max_depth)
-            .follow_links(false)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
             let path = entry.path();
+            if !path.is_dir() || !is_project(path, &self.markers, &mut 0) {
+                continue;
+            }
 
-            if path.is_dir() && is_project(path, &config.project_markers) {
-                projects.push(Project::new(path.to_path_buf(), &scan_path));
+            let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+            if self.seen.insert(canonical) {
+                self.yielded += 1;
+                return Some(Ok(Project::new(path.to_path_buf(), root)));
             }
         }
     }
+}
+
+/// Run a [`ScanIter`] on a background thread, sending each item as it is
+/// found. The scan stops when `cancel` is cancelled or the receiver is
+/// dropped.
+pub fn scan_channel(
+    config: &Config,
+    cancel: CancelToken,
+) -> mpsc::Receiver<Result<Project, ScanError>> {
+    let (sender, receiver) = mpsc::channel();
+    let iter = scan_iter(config).cancel_on(cancel);
 
-    Ok(projects)
+    std::thread::spawn(move || {
+        for item in iter {
+            if sender.send(item).is_err() {
+                break;
+            }
+        }
+    });
+
+    receiver
 }
 
 #[cfg(test)]
@@ -109,7 +638,7 @@ mod tests {
         fs::create_dir(&git_dir).unwrap();
 
         let markers = vec![".git".to_string(), ".jj".to_string()];
-        assert!(is_project(temp_dir.path(), &markers));
+        assert!(is_project(temp_dir.path(), &markers, &mut 0));
     }
 
     #[test]
@@ -119,7 +648,7 @@ mod tests {
         fs::create_dir(&jj_dir).unwrap();
 
         let markers = vec![".git".to_string(), ".jj".to_string()];
-        assert!(is_project(temp_dir.path(), &markers));
+        assert!(is_project(temp_dir.path(), &markers, &mut 0));
     }
 
     #[test]
@@ -127,7 +656,7 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
 
         let markers = vec![".git".to_string(), ".jj".to_string()];
-        assert!(!is_project(temp_dir.path(), &markers));
+        assert!(!is_project(temp_dir.path(), &markers, &mut 0));
     }
 
     #[test]
@@ -137,7 +666,7 @@ mod tests {
         fs::write(&marker_file, "").unwrap();
 
         let markers = vec!["Cargo.toml".to_string()];
-        assert!(is_project(temp_dir.path(), &markers));
+        assert!(is_project(temp_dir.path(), &markers, &mut 0));
     }
 
     #[test]
@@ -159,9 +688,10 @@ mod tests {
         fs::create_dir(&not_project).unwrap();
 
         let config = Config {
-            scan_paths: vec![temp_dir.path().to_path_buf()],
+            scan_paths: vec![temp_dir.path().into()],
             project_markers: vec![".git".to_string(), ".jj".to_string()],
             max_depth: 2,
+            ..Config::default()
         };
 
         let projects = scan_projects(&config).unwrap();
@@ -186,9 +716,10 @@ mod tests {
 
         // Config with max_depth=3 should not find level4 project
         let config = Config {
-            scan_paths: vec![temp_dir.path().to_path_buf()],
+            scan_paths: vec![temp_dir.path().into()],
             project_markers: vec![".git".to_string()],
             max_depth: 3,
+            ..Config::default()
         };
 
         let projects = scan_projects(&config).unwrap();
@@ -196,15 +727,207 @@ mod tests {
 
         // Config with max_depth=4 should find it
         let config = Config {
-            scan_paths: vec![temp_dir.path().to_path_buf()],
+            scan_paths: vec![temp_dir.path().into()],
             project_markers: vec![".git".to_string()],
             max_depth: 4,
+            ..Config::default()
         };
 
         let projects = scan_projects(&config).unwrap();
         assert_eq!(projects.len(), 1);
     }
 
+    #[test]
+    fn test_scan_collects_stats() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("a/.git")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("b/c")).unwrap();
+        fs::write(temp_dir.path().join("b/file.txt"), "").unwrap();
+
+        let config = Config {
+            scan_paths: vec![
+                temp_dir.path().into(),
+                temp_dir.path().join("missing").into(),
+            ],
+            project_markers: vec![".git".to_string()],
+            max_depth: 3,
+            ..Config::default()
+        };
+
+        let report = scan(&config).unwrap();
+        assert_eq!(report.projects.len(), 1);
+        assert_eq!(report.stats.len(), 2);
+
+        let stats = &report.stats[0];
+        assert!(stats.exists);
+        assert_eq!(stats.projects_found, 1);
+        // root, a, a/.git, b, b/c
+        assert_eq!(stats.dirs_visited, 5);
+        assert!(stats.stat_calls >= stats.dirs_visited);
+
+        assert!(!report.stats[1].exists);
+        assert_eq!(report.stats[1].dirs_visited, 0);
+    }
+
+    #[test]
+    fn test_scan_reports_missing_scan_path() {
+        let config = Config {
+            scan_paths: vec![PathBuf::from("/this/path/does/not/exist").into()],
+            project_markers: vec![".git".to_string()],
+            max_depth: 2,
+            ..Config::default()
+        };
+
+        let report = scan(&config).unwrap();
+        assert_eq!(
+            report.diagnostics,
+            vec![ScanDiagnostic {
+                path: PathBuf::from("/this/path/does/not/exist"),
+                kind: ScanDiagnosticKind::MissingScanPath,
+            }]
+        );
+
+        let strict = Config {
+            strict: true,
+            ..config
+        };
+        assert!(scan(&strict).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_reports_permission_denied() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let locked = temp_dir.path().join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        // Root (or CAP_DAC_OVERRIDE) can read anything, so permission bits
+        // can't produce the error this test is about
+        if fs::read_dir(&locked).is_ok() {
+            fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+            eprintln!("skipping test_scan_reports_permission_denied: running with root privileges");
+            return;
+        }
+
+        let config = Config {
+            scan_paths: vec![temp_dir.path().into()],
+            project_markers: vec![".git".to_string()],
+            max_depth: 2,
+            ..Config::default()
+        };
+        let report = scan(&config).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert_eq!(report.diagnostics.len(), 1);
+        assert_eq!(
+            report.diagnostics[0].kind,
+            ScanDiagnosticKind::PermissionDenied
+        );
+        assert_eq!(report.stats[0].permission_errors, 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_follows_symlinks_when_enabled() {
+        let temp_dir = TempDir::new().unwrap();
+        let scan_root = temp_dir.path().join("projects");
+        let data = temp_dir.path().join("data/client");
+        fs::create_dir_all(data.join("api/.git")).unwrap();
+        fs::create_dir_all(&scan_root).unwrap();
+        std::os::unix::fs::symlink(&data, scan_root.join("client")).unwrap();
+        // A second link to the same place, and a loop back to the scan root
+        std::os::unix::fs::symlink(&data, scan_root.join("client-again")).unwrap();
+        std::os::unix::fs::symlink(&scan_root, scan_root.join("loop")).unwrap();
+
+        let config = Config {
+            scan_paths: vec![scan_root.clone().into()],
+            project_markers: vec![".git".to_string()],
+            max_depth: 4,
+            ..Config::default()
+        };
+        assert!(scan(&config).unwrap().projects.is_empty());
+
+        let config = Config {
+            follow_symlinks: true,
+            ..config
+        };
+        let report = scan(&config).unwrap();
+
+        assert_eq!(report.projects.len(), 1);
+        assert!(report.projects[0].path.starts_with(&scan_root));
+        assert!(
+            report
+                .diagnostics
+                .iter()
+                .any(|d| d.kind == ScanDiagnosticKind::SymlinkLoop)
+        );
+    }
+
+    #[test]
+    fn test_scan_dedupes_overlapping_scan_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("ai/decree-ng/.git")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("web/.git")).unwrap();
+
+        let config = Config {
+            scan_paths: vec![temp_dir.path().into(), temp_dir.path().join("ai").into()],
+            project_markers: vec![".git".to_string()],
+            max_depth: 3,
+            ..Config::default()
+        };
+
+        let report = scan(&config).unwrap();
+
+        let mut display_paths: Vec<&str> =
+            report.projects.iter().map(|p| p.display_path()).collect();
+        display_paths.sort();
+        assert_eq!(display_paths, vec!["decree-ng", "web"]);
+
+        assert_eq!(
+            report.diagnostics,
+            vec![ScanDiagnostic {
+                path: temp_dir.path().join("ai"),
+                kind: ScanDiagnosticKind::OverlappingScanPath {
+                    parent: temp_dir.path().to_path_buf()
+                },
+            }]
+        );
+        // Each project counts once, for the scan path that found it first
+        let found: Vec<usize> = report.stats.iter().map(|s| s.projects_found).collect();
+        assert_eq!(found, vec![2, 0]);
+
+        // The overlap is only a warning, even in strict mode
+        let strict = Config {
+            strict: true,
+            ..config
+        };
+        assert_eq!(scan(&strict).unwrap().projects.len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_counts_broken_symlinks() {
+        let temp_dir = TempDir::new().unwrap();
+        std::os::unix::fs::symlink(
+            temp_dir.path().join("nowhere"),
+            temp_dir.path().join("dangling"),
+        )
+        .unwrap();
+
+        let config = Config {
+            scan_paths: vec![temp_dir.path().into()],
+            project_markers: vec![".git".to_string()],
+            max_depth: 2,
+            ..Config::default()
+        };
+
+        let report = scan(&config).unwrap();
+        assert_eq!(report.stats[0].broken_symlinks, 1);
+    }
+
     #[test]
     fn test_scan_projects_empty_when_no_markers() {
         let temp_dir = TempDir::new().unwrap();
@@ -214,12 +937,163 @@ mod tests {
         fs::create_dir(temp_dir.path().join("dir2")).unwrap();
 
         let config = Config {
-            scan_paths: vec![temp_dir.path().to_path_buf()],
+            scan_paths: vec![temp_dir.path().into()],
             project_markers: vec![".git".to_string()],
             max_depth: 2,
+            ..Config::default()
         };
 
         let projects = scan_projects(&config).unwrap();
         assert_eq!(projects.len(), 0);
     }
+
+    #[test]
+    fn test_scan_skips_excluded_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        for dir in [
+            "app",
+            "web/node_modules/dep",
+            "archive/old",
+            "archive-not/new",
+        ] {
+            fs::create_dir_all(temp_dir.path().join(dir).join(".git")).unwrap();
+        }
+
+        let config = Config {
+            scan_paths: vec![temp_dir.path().into()],
+            project_markers: vec![".git".to_string()],
+            max_depth: 4,
+            exclude: vec![
+                "node_modules".to_string(),
+                format!("{}/archive", temp_dir.path().display()),
+            ],
+            ..Config::default()
+        };
+
+        let mut found: Vec<String> = scan_projects(&config)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.display_path)
+            .collect();
+        found.sort();
+        assert_eq!(found, vec!["app", "archive-not/new"]);
+
+        let invalid = Config {
+            exclude: vec!["[".to_string()],
+            ..config
+        };
+        assert!(matches!(
+            scan(&invalid),
+            Err(ScanError::InvalidExclude { .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_iter_skips_symlinked_nested_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("projects");
+        fs::create_dir_all(root.join("ai/decree-ng/.git")).unwrap();
+        fs::create_dir_all(root.join("web/.git")).unwrap();
+        let link = temp_dir.path().join("ai-link");
+        std::os::unix::fs::symlink(root.join("ai"), &link).unwrap();
+
+        let config = Config {
+            scan_paths: vec![root.as_path().into(), link.as_path().into()],
+            project_markers: vec![".git".to_string()],
+            max_depth: 3,
+            ..Config::default()
+        };
+
+        let sorted = |projects: Vec<Project>| {
+            let mut paths: Vec<(PathBuf, String)> = projects
+                .into_iter()
+                .map(|p| (p.path, p.display_path))
+                .collect();
+            paths.sort();
+            paths
+        };
+        let projects = sorted(scan_iter(&config).filter_map(Result::ok).collect());
+
+        assert_eq!(projects, sorted(scan(&config).unwrap().projects));
+        assert_eq!(
+            projects,
+            vec![
+                (link.join("decree-ng"), "decree-ng".to_string()),
+                (root.join("web"), "web".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_iter_matches_scan() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("ai/decree-ng/.git")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("web/.git")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("tools/.git")).unwrap();
+
+        let config = Config {
+            scan_paths: vec![
+                temp_dir.path().into(),
+                temp_dir.path().join("ai").into(),
+                PathBuf::from("/this/path/does/not/exist").into(),
+            ],
+            project_markers: vec![".git".to_string()],
+            max_depth: 3,
+            ..Config::default()
+        };
+
+        let (projects, errors): (Vec<_>, Vec<_>) = scan_iter(&config).partition(|r| r.is_ok());
+        let mut display_paths: Vec<String> = projects
+            .into_iter()
+            .map(|r| r.unwrap().display_path)
+            .collect();
+        display_paths.sort();
+        assert_eq!(display_paths, vec!["decree-ng", "tools", "web"]);
+        assert!(matches!(
+            errors[..],
+            [
+                Err(ScanError::Diagnostic(ScanDiagnostic {
+                    kind: ScanDiagnosticKind::OverlappingScanPath { .. },
+                    ..
+                })),
+                Err(ScanError::Diagnostic(ScanDiagnostic {
+                    kind: ScanDiagnosticKind::MissingScanPath,
+                    ..
+                }))
+            ]
+        ));
+        assert!(errors[0].as_ref().unwrap_err().is_warning());
+        assert!(!errors[1].as_ref().unwrap_err().is_warning());
+
+        // The channel variant yields the same projects
+        let received = scan_channel(&config, CancelToken::new())
+            .into_iter()
+            .filter(|r| r.is_ok())
+            .count();
+        assert_eq!(received, 3);
+    }
+
+    #[test]
+    fn test_scan_iter_limit_and_cancel() {
+        let temp_dir = TempDir::new().unwrap();
+        for name in ["a", "b", "c"] {
+            fs::create_dir_all(temp_dir.path().join(name).join(".git")).unwrap();
+        }
+
+        let config = Config {
+            scan_paths: vec![temp_dir.path().into()],
+            project_markers: vec![".git".to_string()],
+            max_depth: 2,
+            ..Config::default()
+        };
+
+        assert_eq!(scan_iter(&config).limit(2).count(), 2);
+
+        let cancel = CancelToken::new();
+        let mut iter = scan_iter(&config).cancel_on(cancel.clone());
+        assert!(iter.next().is_some());
+        cancel.cancel();
+        assert!(iter.next().is_none());
+    }
 }
@@ -0,0 +1,353 @@
+use crate::config::Config;
+#[cfg(unix)]
+use crate::daemon::{self, Client, Request};
+use crate::history::History;
+use crate::matcher::{Match, rank};
+use crate::resolve::enclosing_project;
+use crate::scanner::Project;
+use crate::state::state_dir;
+use crate::tags::{TagStore, split_tag_terms};
+use crate::watch::{ProjectWatcher, RESCAN_INTERVAL};
+use anyhow::Result;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// A JSON-RPC error object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const INTERNAL_ERROR: i64 = -32603;
+
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        Self::new(
+            Self::METHOD_NOT_FOUND,
+            format!("Unknown method: {}", method),
+        )
+    }
+
+    pub fn internal(err: anyhow::Error) -> Self {
+        Self::new(Self::INTERNAL_ERROR, format!("{:#}", err))
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+/// Deserialize a request's `params`; missing params read as `{}`
+pub fn params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params)
+        .map_err(|err| RpcError::new(RpcError::INVALID_PARAMS, err.to_string()))
+}
+
+/// Read newline-delimited JSON-RPC 2.0 messages from `input` until it ends,
+/// answering each request with `handle(method, params)`. Notifications,
+/// which have no `id`, are handled but get no response.
+pub fn serve_json_rpc<R, W, F>(input: R, mut output: W, mut handle: F) -> Result<()>
+where
+    R: BufRead,
+    W: Write,
+    F: FnMut(&str, Value) -> Result<Value, RpcError>,
+{
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (id, result) = match serde_json::from_str::<Value>(&line) {
+            Err(err) => (
+                Value::Null,
+                Err(RpcError::new(RpcError::PARSE_ERROR, err.to_string())),
+            ),
+            Ok(Value::Object(mut message)) => {
+                let id = message.remove("id");
+                let params = message.remove("params").unwrap_or(Value::Null);
+                let result = match message.get("method").and_then(Value::as_str) {
+                    Some(method) => handle(method, params),
+                    None => Err(RpcError::new(RpcError::INVALID_REQUEST, "Missing method")),
+                };
+                match id {
+                    Some(id) => (id, result),
+                    None => continue,
+                }
+            }
+            Ok(_) => (
+                Value::Null,
+                Err(RpcError::new(
+                    RpcError::INVALID_REQUEST,
+                    "Expected an object",
+                )),
+            ),
+        };
+
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(err) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": err.code, "message": err.message },
+            }),
+        };
+        serde_json::to_writer(&mut output, &response)?;
+        output.write_all(b"\n")?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QueryParams {
+    pattern: String,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PathParams {
+    path: PathBuf,
+}
+
+fn project_json(project: &Project) -> Value {
+    json!({ "path": project.path, "display_path": project.display_path })
+}
+
+/// The `pj serve --stdio` server: pj's projects, ranking, tags and history
+/// for editor plugins
+pub struct Server {
+    config: Config,
+    watcher: ProjectWatcher,
+    last_scan: Instant,
+    state_dir: PathBuf,
+    /// Socket of a `pj daemon` that visits are recorded through when it runs
+    #[cfg(unix)]
+    daemon_socket: Option<PathBuf>,
+}
+
+impl Server {
+    /// Scan and watch `config`'s scan paths, using the pj state directory
+    /// and recording visits through `pj daemon` when it is running
+    pub fn open(config: Config) -> Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            daemon_socket: daemon::socket_path().ok(),
+            ..Self::new(config, state_dir()?)?
+        })
+    }
+
+    /// Like [`Server::open`], keeping history and tags in `state_dir` and
+    /// writing visits there directly
+    pub fn new(config: Config, state_dir: PathBuf) -> Result<Self> {
+        Ok(Self {
+            watcher: ProjectWatcher::new(config.clone())?,
+            last_scan: Instant::now(),
+            config,
+            state_dir,
+            #[cfg(unix)]
+            daemon_socket: None,
+        })
+    }
+
+    /// Answer requests on stdin until it closes
+    pub fn serve_stdio(&mut self) -> Result<()> {
+        let stdin = std::io::stdin().lock();
+        let stdout = std::io::stdout().lock();
+        serve_json_rpc(stdin, stdout, |method, params| self.handle(method, params))
+    }
+
+    /// The projects, after applying filesystem changes seen so far. Without
+    /// a complete watch they are rescanned every [`RESCAN_INTERVAL`] instead.
+    pub fn projects(&mut self) -> Result<&[Project]> {
+        if !self.watcher.is_complete() && self.last_scan.elapsed() >= RESCAN_INTERVAL {
+            self.watcher.rescan()?;
+            self.last_scan = Instant::now();
+        } else {
+            self.watcher.poll(Duration::ZERO)?;
+        }
+        Ok(self.watcher.projects())
+    }
+
+    /// Answer one request
+    pub fn handle(&mut self, method: &str, params_value: Value) -> Result<Value, RpcError> {
+        match method {
+            "list" => {
+                let projects = self.projects().map_err(RpcError::internal)?;
+                Ok(projects.iter().map(project_json).collect())
+            }
+            "query" => {
+                let QueryParams { pattern, limit } = params(params_value)?;
+                let matches = self.search(&pattern).map_err(RpcError::internal)?;
+                Ok(matches
+                    .into_iter()
+                    .take(limit.unwrap_or(usize::MAX))
+                    .map(|m| {
+                        json!({
+                            "path": m.project.path,
+                            "display_path": m.project.display_path,
+                            "score": m.score.total(),
+                            "indices": m.indices,
+                        })
+                    })
+                    .collect())
+            }
+            "record-visit" => {
+                let PathParams { path } = params(params_value)?;
+                self.record_visit(&path).map_err(RpcError::internal)?;
+                Ok(Value::Null)
+            }
+            "resolve" => {
+                let PathParams { path } = params(params_value)?;
+                let project = self.resolve(&path).map_err(RpcError::internal)?;
+                Ok(project.as_ref().map_or(Value::Null, project_json))
+            }
+            _ => Err(RpcError::method_not_found(method)),
+        }
+    }
+
+    /// Rank the projects matching `pattern`, with `#tag` terms filtering
+    /// as on the command line
+    pub fn search(&mut self, pattern: &str) -> Result<Vec<Match>> {
+        let (tags, rest) = split_tag_terms(pattern);
+        let mut projects = self.projects()?.to_vec();
+        if !tags.is_empty() {
+            projects = self.tags()?.filter(projects, &tags);
+        }
+
+        Ok(rank(projects, &rest, &self.config.ranking, self.history()?))
+    }
+
+    /// The project containing `path`, a file or directory
+    pub fn resolve(&mut self, path: &Path) -> Result<Option<Project>> {
+        let path = std::path::absolute(path)?;
+        let projects = self.projects()?;
+
+        let project = enclosing_project(projects, &path).or_else(|| {
+            let canonical = path.canonicalize().ok()?;
+            enclosing_project(projects, &canonical)
+        });
+        Ok(project.cloned())
+    }
+
+    /// The tags assigned with `pj tag`
+    pub fn tags(&self) -> Result<TagStore> {
+        TagStore::load(self.state_dir.join("tags.toml"))
+    }
+
+    /// The visits recorded by pj, used for ranking
+    pub fn history(&self) -> Result<History> {
+        History::load(self.state_dir.join("history.toml"))
+    }
+
+    /// Add a visit to `project` to the history, through the daemon if it is
+    /// running so the two don't overwrite each other's history writes
+    pub fn record_visit(&self, project: &Path) -> Result<()> {
+        #[cfg(unix)]
+        if let Some(mut client) = self.daemon_socket.as_deref().and_then(Client::connect_to) {
+            let request = Request::RecordVisit {
+                path: project.to_path_buf(),
+            };
+            if client.request(&request).is_ok() {
+                return Ok(());
+            }
+        }
+
+        let mut history = self.history()?;
+        history.record(project);
+        history.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn run(server: &mut Server, input: &str) -> Vec<Value> {
+        let mut output = Vec::new();
+        serve_json_rpc(input.as_bytes(), &mut output, |method, params| {
+            server.handle(method, params)
+        })
+        .unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_serve_requests() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("projects");
+        fs::create_dir_all(root.join("api/src")).unwrap();
+        fs::create_dir_all(root.join("api/.git")).unwrap();
+        fs::create_dir_all(root.join("web/.git")).unwrap();
+        let config = Config {
+            scan_paths: vec![root.as_path().into()],
+            ..Config::default()
+        };
+        let mut server = Server::new(config, temp_dir.path().to_path_buf()).unwrap();
+
+        let file = root.join("api/src/main.rs");
+        let input = format!(
+            concat!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"query","params":{{"pattern":"ap"}}}}"#,
+                "\n",
+                r#"{{"jsonrpc":"2.0","id":2,"method":"resolve","params":{{"path":{:?}}}}}"#,
+                "\n",
+                r#"{{"jsonrpc":"2.0","method":"record-visit","params":{{"path":{:?}}}}}"#,
+                "\n",
+                r#"{{"jsonrpc":"2.0","id":3,"method":"list"}}"#,
+                "\n",
+                r#"{{"jsonrpc":"2.0","id":4,"method":"nope"}}"#,
+                "\n",
+                r#"{{"jsonrpc":"2.0","id":5,"method":"query","params":{{}}}}"#,
+                "\nnot json\n",
+            ),
+            file,
+            root.join("api"),
+        );
+        let responses = run(&mut server, &input);
+
+        assert_eq!(responses.len(), 6);
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(responses[0]["result"][0]["display_path"], "api");
+        assert_eq!(responses[0]["result"][0]["indices"], json!([0, 1]));
+        assert!(responses[0]["result"][0]["score"].is_i64());
+
+        assert_eq!(responses[1]["result"]["display_path"], "api");
+        assert_eq!(responses[2]["result"].as_array().unwrap().len(), 2);
+        assert_eq!(responses[3]["error"]["code"], RpcError::METHOD_NOT_FOUND);
+        assert_eq!(responses[4]["error"]["code"], RpcError::INVALID_PARAMS);
+        assert_eq!(responses[5]["error"]["code"], RpcError::PARSE_ERROR);
+        assert_eq!(responses[5]["id"], Value::Null);
+
+        let history = History::load(temp_dir.path().join("history.toml")).unwrap();
+        assert_eq!(history.get(&root.join("api")).unwrap().rank, 1.0);
+    }
+}
@@ -0,0 +1,245 @@
+use crate::scanner::Project;
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use std::process::Command;
+
+/// Terminal multiplexer used by `pj session`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Multiplexer {
+    #[default]
+    Tmux,
+    Zellij,
+}
+
+/// A tmux window created in a new session
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SessionWindow {
+    pub name: String,
+    /// Command to run in the window (default: the login shell)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
+
+/// The `[session]` config table
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SessionConfig {
+    pub multiplexer: Multiplexer,
+    /// tmux windows to create, in order, when a session doesn't exist yet
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub windows: Vec<SessionWindow>,
+    /// zellij layout name or file used when a session doesn't exist yet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zellij_layout: Option<String>,
+}
+
+/// Session name for a project: its display path, with the characters tmux
+/// treats specially (`.` and `:`) and zellij rejects (`/`) replaced
+pub fn session_name(project: &Project) -> String {
+    project.display_path().replace(['.', ':', '/'], "_")
+}
+
+/// Run a multiplexer command, failing on a nonzero exit
+fn run(mut command: Command) -> Result<()> {
+    let status = command
+        .status()
+        .with_context(|| format!("Failed to run {:?}", command.get_program()))?;
+
+    if !status.success() {
+        bail!("{:?} failed ({})", command, status);
+    }
+
+    Ok(())
+}
+
+/// Names of running sessions. An absent server means no sessions.
+pub fn live_sessions(multiplexer: Multiplexer) -> Result<HashSet<String>> {
+    let output = match multiplexer {
+        Multiplexer::Tmux => Command::new("tmux")
+            .args(["list-sessions", "-F", "#{session_name}"])
+            .output(),
+        Multiplexer::Zellij => Command::new("zellij")
+            .args(["list-sessions", "--short", "--no-formatting"])
+            .output(),
+    }
+    .context("Failed to list multiplexer sessions")?;
+
+    if !output.status.success() {
+        return Ok(HashSet::new());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect())
+}
+
+/// The tmux commands that create session `name` in `dir` with `windows`
+fn tmux_create_commands(name: &str, dir: &Path, windows: &[SessionWindow]) -> Vec<Command> {
+    let dir = dir.to_string_lossy();
+    let target = format!("={}", name);
+    let mut commands = Vec::new();
+
+    let mut new_session = Command::new("tmux");
+    new_session.args(["new-session", "-d", "-s", name, "-c", &dir]);
+    if let Some(first) = windows.first() {
+        new_session.args(["-n", &first.name]);
+        new_session.args(first.command.as_deref());
+    }
+    commands.push(new_session);
+
+    for window in windows.iter().skip(1) {
+        let mut new_window = Command::new("tmux");
+        new_window.args(["new-window", "-t", &target, "-n", &window.name, "-c", &dir]);
+        new_window.args(window.command.as_deref());
+        commands.push(new_window);
+    }
+
+    if windows.len() > 1 {
+        let mut select = Command::new("tmux");
+        select.args(["select-window", "-t", &format!("{}:^", target)]);
+        commands.push(select);
+    }
+
+    commands
+}
+
+/// Attach to (or switch to, when already inside tmux) the session for
+/// `project`, creating it first if it doesn't exist
+pub fn open_session(config: &SessionConfig, project: &Project) -> Result<()> {
+    let name = session_name(project);
+    let exists = live_sessions(config.multiplexer)?.contains(&name);
+
+    match config.multiplexer {
+        Multiplexer::Tmux => {
+            if !exists {
+                for command in tmux_create_commands(&name, &project.path, &config.windows) {
+                    run(command)?;
+                }
+            }
+
+            let mut attach = Command::new("tmux");
+            if std::env::var_os("TMUX").is_some() {
+                attach.args(["switch-client", "-t", &format!("={}", name)]);
+            } else {
+                attach.args(["attach-session", "-t", &format!("={}", name)]);
+            }
+            run(attach)
+        }
+        Multiplexer::Zellij => {
+            if std::env::var_os("ZELLIJ").is_some() {
+                bail!("Already inside zellij; detach first to open another session");
+            }
+
+            let mut command = Command::new("zellij");
+            command.current_dir(&project.path);
+            if exists {
+                command.args(["attach", &name]);
+            } else {
+                if let Some(layout) = &config.zellij_layout {
+                    command.args(["--layout", layout]);
+                }
+                command.args(["--session", &name]);
+            }
+            run(command)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn args(command: &Command) -> Vec<String> {
+        command
+            .get_args()
+            .map(|a| a.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_session_name() {
+        let project = Project::new(
+            PathBuf::from("/home/user/Projects/example.com"),
+            Path::new("/home/user/Projects"),
+        );
+        assert_eq!(session_name(&project), "example_com");
+    }
+
+    #[test]
+    fn test_session_name_nested_project() {
+        let project = Project::new(
+            PathBuf::from("/home/user/Projects/ai/decree-ng/main"),
+            Path::new("/home/user/Projects"),
+        );
+        assert_eq!(session_name(&project), "ai_decree-ng_main");
+    }
+
+    #[test]
+    fn test_tmux_create_commands_with_windows() {
+        let windows = vec![
+            SessionWindow {
+                name: "editor".to_string(),
+                command: Some("nvim .".to_string()),
+            },
+            SessionWindow {
+                name: "shell".to_string(),
+                command: None,
+            },
+        ];
+
+        let commands = tmux_create_commands("api", Path::new("/p/api"), &windows);
+
+        assert_eq!(commands.len(), 3);
+        assert_eq!(
+            args(&commands[0]),
+            [
+                "new-session",
+                "-d",
+                "-s",
+                "api",
+                "-c",
+                "/p/api",
+                "-n",
+                "editor",
+                "nvim ."
+            ]
+        );
+        assert_eq!(
+            args(&commands[1]),
+            ["new-window", "-t", "=api", "-n", "shell", "-c", "/p/api"]
+        );
+        assert_eq!(args(&commands[2]), ["select-window", "-t", "=api:^"]);
+    }
+
+    #[test]
+    fn test_tmux_create_commands_without_windows() {
+        let commands = tmux_create_commands("api", Path::new("/p/api"), &[]);
+
+        assert_eq!(commands.len(), 1);
+        assert_eq!(
+            args(&commands[0]),
+            ["new-session", "-d", "-s", "api", "-c", "/p/api"]
+        );
+    }
+
+    #[test]
+    fn test_session_config_from_toml() {
+        let config: SessionConfig = toml::from_str(
+            r#"
+            multiplexer = "zellij"
+            zellij_layout = "compact"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.multiplexer, Multiplexer::Zellij);
+        assert_eq!(config.zellij_layout.as_deref(), Some("compact"));
+        assert!(config.windows.is_empty());
+    }
+}
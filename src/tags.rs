@@ -0,0 +1,202 @@
+use crate::project_file::ProjectFile;
+use crate::scanner::Project;
+use crate::state::{state_dir, write_atomic};
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Normalize a tag as typed by the user: `#OnCall` and `oncall` are the same tag
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_lowercase()
+}
+
+/// Split `#tag` terms out of a search pattern. Returns the tags and the
+/// rest of the pattern, e.g. `#oncall api` -> (["oncall"], "api").
+pub fn split_tag_terms(pattern: &str) -> (Vec<String>, String) {
+    let (tags, rest): (Vec<&str>, Vec<&str>) = pattern
+        .split_whitespace()
+        .partition(|term| term.len() > 1 && term.starts_with('#'));
+
+    (
+        tags.into_iter().map(normalize_tag).collect(),
+        rest.join(" "),
+    )
+}
+
+/// Tags assigned with `pj tag add`, keyed by project path
+#[derive(Debug, Default)]
+pub struct TagStore {
+    path: PathBuf,
+    tags: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl TagStore {
+    /// Open the tag store in the pj state directory
+    pub fn open() -> Result<Self> {
+        Self::load(state_dir()?.join("tags.toml"))
+    }
+
+    /// Load a tag store from `path`; a missing file is an empty store
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let tags = match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)
+                .with_context(|| format!("Failed to parse {}", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err).context("Failed to read tag store"),
+        };
+
+        Ok(Self { path, tags })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let contents = toml::to_string(&self.tags).context("Failed to serialize tags")?;
+        write_atomic(&self.path, &contents).context("Failed to write tag store")
+    }
+
+    fn key(project: &Path) -> String {
+        project.to_string_lossy().to_string()
+    }
+
+    /// Add `tag` to `project`. Returns false if it was already there.
+    pub fn add(&mut self, project: &Path, tag: &str) -> bool {
+        self.tags
+            .entry(Self::key(project))
+            .or_default()
+            .insert(normalize_tag(tag))
+    }
+
+    /// Remove `tag` from `project`. Returns false if it wasn't there.
+    pub fn remove(&mut self, project: &Path, tag: &str) -> bool {
+        let key = Self::key(project);
+        let Some(tags) = self.tags.get_mut(&key) else {
+            return false;
+        };
+
+        let removed = tags.remove(&normalize_tag(tag));
+        if tags.is_empty() {
+            self.tags.remove(&key);
+        }
+        removed
+    }
+
+    /// All tags of `project`: those in the store plus any in its `.pj.toml`
+    pub fn tags_for(&self, project: &Path) -> BTreeSet<String> {
+        let mut tags = self
+            .tags
+            .get(&Self::key(project))
+            .cloned()
+            .unwrap_or_default();
+
+        if let Ok(Some((_, project_file))) = ProjectFile::load(project) {
+            tags.extend(project_file.tags.iter().map(|t| normalize_tag(t)));
+        }
+
+        tags
+    }
+
+    /// Every tag with the projects carrying it, among `projects`
+    pub fn all_tags(&self, projects: &[Project]) -> BTreeMap<String, Vec<PathBuf>> {
+        let mut all: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+
+        for project in projects {
+            for tag in self.tags_for(&project.path) {
+                all.entry(tag).or_default().push(project.path.clone());
+            }
+        }
+
+        all
+    }
+
+    /// Keep only the projects that carry every one of `tags`
+    pub fn filter(&self, projects: Vec<Project>, tags: &[String]) -> Vec<Project> {
+        if tags.is_empty() {
+            return projects;
+        }
+
+        projects
+            .into_iter()
+            .filter(|project| {
+                let project_tags = self.tags_for(&project.path);
+                tags.iter()
+                    .all(|tag| project_tags.contains(&normalize_tag(tag)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_split_tag_terms() {
+        assert_eq!(
+            split_tag_terms("#oncall api #Client-Acme"),
+            (
+                vec!["oncall".to_string(), "client-acme".to_string()],
+                "api".to_string()
+            )
+        );
+        assert_eq!(split_tag_terms("api # x"), (vec![], "api # x".to_string()));
+    }
+
+    #[test]
+    fn test_tag_store_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("tags.toml");
+        let project = Path::new("/p/api");
+
+        let mut store = TagStore::load(path.clone()).unwrap();
+        assert!(store.add(project, "#oncall"));
+        assert!(!store.add(project, "OnCall"));
+        assert!(store.add(project, "archived"));
+        store.save().unwrap();
+
+        let mut store = TagStore::load(path).unwrap();
+        assert_eq!(
+            store.tags_for(project).into_iter().collect::<Vec<_>>(),
+            vec!["archived", "oncall"]
+        );
+        assert!(store.remove(project, "archived"));
+        assert!(!store.remove(project, "archived"));
+    }
+
+    #[test]
+    fn test_filter_uses_store_and_project_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let api = temp_dir.path().join("api");
+        let web = temp_dir.path().join("web");
+        fs::create_dir_all(&api).unwrap();
+        fs::create_dir_all(&web).unwrap();
+        fs::write(
+            web.join(crate::project_file::PROJECT_FILE),
+            "tags = [\"oncall\"]\n",
+        )
+        .unwrap();
+
+        let mut store = TagStore::load(temp_dir.path().join("tags.toml")).unwrap();
+        store.add(&api, "oncall");
+        store.add(&api, "client-acme");
+
+        let projects = vec![
+            Project::new(api.clone(), temp_dir.path()),
+            Project::new(web.clone(), temp_dir.path()),
+        ];
+
+        let oncall = store.filter(projects.clone(), &["oncall".to_string()]);
+        assert_eq!(oncall.len(), 2);
+
+        let both = store.filter(
+            projects.clone(),
+            &["oncall".to_string(), "client-acme".to_string()],
+        );
+        assert_eq!(both.len(), 1);
+        assert_eq!(both[0].path, api);
+
+        let all = store.all_tags(&projects);
+        assert_eq!(all["oncall"], vec![api.clone(), web]);
+        assert_eq!(all["client-acme"], vec![api]);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::config::Config;
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Placeholder replaced with the project name in template file names and contents
+const NAME_PLACEHOLDER: &str = "{{name}}";
+
+/// The directory holding a named template, e.g. ~/.config/pj/templates/rust-cli
+pub fn template_dir(template: &str) -> Result<PathBuf> {
+    Ok(Config::config_dir()?.join("templates").join(template))
+}
+
+/// Copy `src` into `dest`, substituting the project name in file names and
+/// in the contents of UTF-8 files. Other files are copied byte for byte, and
+/// symlinks are recreated as symlinks.
+fn copy_template(src: &Path, dest: &Path, name: &str) -> Result<()> {
+    fs::create_dir_all(dest).with_context(|| format!("Failed to create {}", dest.display()))?;
+
+    for entry in fs::read_dir(src).with_context(|| format!("Failed to read {}", src.display()))? {
+        let entry = entry?;
+        let file_name = entry
+            .file_name()
+            .to_string_lossy()
+            .replace(NAME_PLACEHOLDER, name);
+        let target = dest.join(file_name);
+
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            copy_template(&entry.path(), &target, name)?;
+            continue;
+        }
+        #[cfg(unix)]
+        if file_type.is_symlink() {
+            let link = fs::read_link(entry.path())?;
+            std::os::unix::fs::symlink(&link, &target)
+                .with_context(|| format!("Failed to create {}", target.display()))?;
+            continue;
+        }
+
+        let bytes = fs::read(entry.path())?;
+        let contents = match String::from_utf8(bytes) {
+            Ok(text) => text.replace(NAME_PLACEHOLDER, name).into_bytes(),
+            Err(err) => err.into_bytes(),
+        };
+        fs::write(&target, contents)
+            .with_context(|| format!("Failed to write {}", target.display()))?;
+        fs::set_permissions(&target, entry.metadata()?.permissions())?;
+    }
+
+    Ok(())
+}
+
+/// Run the `post_create` hook in the new project, sending its output to
+/// stderr so that stdout only carries the project path
+fn run_post_create(hook: &str, dir: &Path) -> Result<()> {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(hook)
+        .current_dir(dir)
+        .stdout(Stdio::from(std::io::stderr()))
+        .status()
+        .context("Failed to run post_create hook")?;
+
+    if !status.success() {
+        bail!("post_create hook `{}` failed ({})", hook, status);
+    }
+
+    Ok(())
+}
+
+/// Create project `name` inside `parent` (default: the first scan path),
+/// optionally from a template directory, then run the `post_create` hook.
+/// Returns the new project's path.
+pub fn create_project(
+    config: &Config,
+    name: &str,
+    template: Option<&Path>,
+    parent: Option<&Path>,
+) -> Result<PathBuf> {
+    let relative = Path::new(name);
+    if name.is_empty()
+        || relative
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        bail!("Invalid project name: {}", name);
+    }
+
+    let parent = match parent {
+        Some(parent) => parent.to_path_buf(),
+        None => config
+            .scan_paths
+            .first()
+            .map(|scan_path| scan_path.expanded())
+            .context("No scan_paths configured")?,
+    };
+    let dest = parent.join(relative);
+
+    if dest.exists() {
+        bail!("Destination already exists: {}", dest.display());
+    }
+
+    // Substitute only the last component, so `pj new ai/app` names the project `app`
+    let project_name = relative
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| name.to_string());
+
+    if let Some(template) = template
+        && !template.is_dir()
+    {
+        bail!("Template not found: {}", template.display());
+    }
+
+    let populate = || -> Result<()> {
+        match template {
+            Some(template) => copy_template(template, &dest, &project_name)?,
+            None => fs::create_dir_all(&dest)
+                .with_context(|| format!("Failed to create {}", dest.display()))?,
+        }
+        if let Some(hook) = &config.post_create {
+            run_post_create(hook, &dest)?;
+        }
+        Ok(())
+    };
+
+    // Don't leave a half-made project behind to block a retry
+    if let Err(err) = populate() {
+        let _ = fs::remove_dir_all(&dest);
+        return Err(err);
+    }
+
+    Ok(dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_create_project_from_template() {
+        let temp_dir = TempDir::new().unwrap();
+        let template = temp_dir.path().join("template");
+        fs::create_dir_all(template.join("src")).unwrap();
+        fs::write(
+            template.join("Cargo.toml"),
+            "[package]\nname = \"{{name}}\"\n",
+        )
+        .unwrap();
+        fs::write(template.join("src/{{name}}.rs"), "// {{name}}\n").unwrap();
+        fs::write(template.join("logo.bin"), [0xff, 0xfe, 0x00]).unwrap();
+
+        let root = temp_dir.path().join("projects");
+        let config = Config {
+            scan_paths: vec![root.clone().into()],
+            post_create: Some("touch .created".to_string()),
+            ..Config::default()
+        };
+
+        let dest = create_project(&config, "ai/widget", Some(&template), None).unwrap();
+
+        assert_eq!(dest, root.join("ai/widget"));
+        assert_eq!(
+            fs::read_to_string(dest.join("Cargo.toml")).unwrap(),
+            "[package]\nname = \"widget\"\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dest.join("src/widget.rs")).unwrap(),
+            "// widget\n"
+        );
+        assert_eq!(fs::read(dest.join("logo.bin")).unwrap(), [0xff, 0xfe, 0x00]);
+        assert!(dest.join(".created").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_create_project_keeps_symlinks() {
+        let temp_dir = TempDir::new().unwrap();
+        let template = temp_dir.path().join("template");
+        fs::create_dir_all(&template).unwrap();
+        fs::write(template.join("README.md"), "# {{name}}\n").unwrap();
+        std::os::unix::fs::symlink("README.md", template.join("README")).unwrap();
+
+        let config = Config {
+            scan_paths: vec![temp_dir.path().join("projects").into()],
+            ..Config::default()
+        };
+        let dest = create_project(&config, "widget", Some(&template), None).unwrap();
+
+        assert_eq!(
+            fs::read_link(dest.join("README")).unwrap(),
+            Path::new("README.md")
+        );
+    }
+
+    #[test]
+    fn test_create_project_cleans_up_after_failure() {
+        let temp_dir = TempDir::new().unwrap();
+        let template = temp_dir.path().join("template");
+        fs::create_dir_all(&template).unwrap();
+        fs::write(template.join("README.md"), "# {{name}}\n").unwrap();
+
+        let mut config = Config {
+            scan_paths: vec![temp_dir.path().join("projects").into()],
+            post_create: Some("exit 1".to_string()),
+            ..Config::default()
+        };
+        assert!(create_project(&config, "widget", Some(&template), None).is_err());
+        assert!(!temp_dir.path().join("projects/widget").exists());
+
+        // Nothing left behind blocks a retry
+        config.post_create = None;
+        assert!(create_project(&config, "widget", Some(&template), None).is_ok());
+    }
+
+    #[test]
+    fn test_create_project_rejects_existing_and_unsafe_names() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("taken")).unwrap();
+
+        let config = Config {
+            scan_paths: vec![temp_dir.path().into()],
+            ..Config::default()
+        };
+
+        assert!(create_project(&config, "taken", None, None).is_err());
+        assert!(create_project(&config, "../escape", None, None).is_err());
+        assert!(create_project(&config, "/abs", None, None).is_err());
+    }
+}
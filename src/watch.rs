@@ -0,0 +1,450 @@
+use crate::config::Config;
+use crate::scanner::{Exclude, Project, excludes, scan};
+use anyhow::{Context, Result, bail};
+use notify::event::{EventKind, ModifyKind};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+use walkdir::WalkDir;
+
+/// A project appearing or disappearing
+#[derive(Debug, Clone)]
+pub enum Change {
+    Added(Project),
+    Removed(Project),
+}
+
+/// The project list of a config, updated one changed path at a time
+pub struct ProjectIndex {
+    config: Config,
+    excludes: Vec<Exclude>,
+    /// Expanded scan paths, with whether to follow symlinks below them
+    roots: Vec<(PathBuf, bool)>,
+    projects: Vec<Project>,
+}
+
+impl ProjectIndex {
+    /// Scan `config`'s scan paths
+    pub fn new(config: Config) -> Result<Self> {
+        let excludes = excludes(&config)?;
+        let projects = scan(&config)?.projects;
+        let roots = config
+            .scan_paths
+            .iter()
+            .map(|scan_path| (scan_path.expanded(), config.follows_symlinks(scan_path)))
+            .collect();
+
+        Ok(Self {
+            config,
+            excludes,
+            roots,
+            projects,
+        })
+    }
+
+    pub fn projects(&self) -> &[Project] {
+        &self.projects
+    }
+
+    /// The most specific scan path containing `path`, whether to follow
+    /// symlinks below it, and the depth of `path` below it
+    fn locate(&self, path: &Path) -> Option<(&Path, bool, usize)> {
+        self.roots
+            .iter()
+            .filter_map(|(root, follow_links)| {
+                let depth = path.strip_prefix(root).ok()?.components().count();
+                Some((root.as_path(), *follow_links, depth))
+            })
+            .min_by_key(|(_, _, depth)| *depth)
+    }
+
+    fn is_root(&self, path: &Path) -> bool {
+        self.roots.iter().any(|(root, _)| root == path)
+    }
+
+    fn is_project(&self, dir: &Path) -> bool {
+        self.config
+            .project_markers
+            .iter()
+            .any(|marker| dir.join(marker).exists())
+    }
+
+    /// Walk the directories at or below `dir`, which is `depth` below its scan
+    /// path, that a scan would visit
+    fn walk(
+        &self,
+        dir: &Path,
+        follow_links: bool,
+        depth: usize,
+    ) -> impl Iterator<Item = PathBuf> + '_ {
+        let remaining = self.config.max_depth.checked_sub(depth);
+
+        remaining
+            .filter(|_| dir.is_dir())
+            .map(|remaining| {
+                WalkDir::new(dir)
+                    .max_depth(remaining)
+                    .follow_links(follow_links)
+                    .into_iter()
+                    .filter_entry(|entry| {
+                        self.is_root(entry.path())
+                            || !self.excludes.iter().any(|e| e.matches(entry.path()))
+                    })
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.path().is_dir())
+                    .map(|entry| entry.into_path())
+            })
+            .into_iter()
+            .flatten()
+    }
+
+    /// Directories to watch so that projects appearing at or below `dir` are
+    /// noticed. Marker directories such as `.git` are left out: their
+    /// contents never decide whether something is a project.
+    pub fn watch_dirs(&self, dir: &Path) -> Vec<PathBuf> {
+        let Some((_, follow_links, depth)) = self.locate(dir) else {
+            return Vec::new();
+        };
+        let markers = &self.config.project_markers;
+
+        self.walk(dir, follow_links, depth)
+            .filter(|path| {
+                !path
+                    .file_name()
+                    .is_some_and(|name| markers.iter().any(|m| name == m.as_str()))
+            })
+            .collect()
+    }
+
+    /// Re-examine `changed` after it was created, removed or renamed: its
+    /// parent may have gained or lost a marker, and projects may have
+    /// appeared or vanished below it
+    pub fn refresh(&mut self, changed: &Path) -> Vec<Change> {
+        let Some((root, follow_links, depth)) = self.locate(changed) else {
+            return Vec::new();
+        };
+        let root = root.to_path_buf();
+        let parent = changed.parent().filter(|_| depth > 0);
+
+        let mut found: Vec<PathBuf> = self.walk(changed, follow_links, depth).collect();
+        if let Some(parent) = parent {
+            found.push(parent.to_path_buf());
+        }
+        found.retain(|dir| self.is_project(dir));
+
+        let in_scope = |path: &Path| path.starts_with(changed) || Some(path) == parent;
+        let mut changes = Vec::new();
+
+        self.projects.retain(|project| {
+            let keep = !in_scope(&project.path) || found.contains(&project.path);
+            if !keep {
+                changes.push(Change::Removed(project.clone()));
+            }
+            keep
+        });
+
+        found.retain(|dir| !self.projects.iter().any(|p| &p.path == dir));
+        if found.is_empty() {
+            return changes;
+        }
+
+        // As in a scan, a project reachable through several symlinks is
+        // listed once
+        let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let mut seen: HashSet<PathBuf> = self.projects.iter().map(|p| canonical(&p.path)).collect();
+        for dir in found {
+            if seen.insert(canonical(&dir)) {
+                let project = Project::new(dir, &root);
+                changes.push(Change::Added(project.clone()));
+                self.projects.push(project);
+            }
+        }
+
+        changes
+    }
+
+    /// Scan everything again, returning what changed
+    pub fn rescan(&mut self) -> Result<Vec<Change>> {
+        let projects = scan(&self.config)?.projects;
+        let old: HashSet<&Path> = self.projects.iter().map(|p| p.path.as_path()).collect();
+        let new: HashSet<&Path> = projects.iter().map(|p| p.path.as_path()).collect();
+
+        let mut changes: Vec<Change> = self
+            .projects
+            .iter()
+            .filter(|p| !new.contains(p.path.as_path()))
+            .map(|p| Change::Removed(p.clone()))
+            .collect();
+        changes.extend(
+            projects
+                .iter()
+                .filter(|p| !old.contains(p.path.as_path()))
+                .map(|p| Change::Added(p.clone())),
+        );
+
+        self.projects = projects;
+        Ok(changes)
+    }
+}
+
+/// How often to rescan when not every directory can be watched
+pub const RESCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Keeps a [`ProjectIndex`] current by watching the scan paths with inotify
+/// (or the platform's equivalent), one non-recursive watch per directory
+/// within `max_depth` that isn't excluded.
+///
+/// If the OS runs out of watches, or watching can't start at all, the
+/// remaining directories go unwatched and [`ProjectWatcher::is_complete`]
+/// turns false; callers should then call [`ProjectWatcher::rescan`] every
+/// [`RESCAN_INTERVAL`].
+pub struct ProjectWatcher {
+    index: ProjectIndex,
+    watcher: Option<RecommendedWatcher>,
+    events: mpsc::Receiver<notify::Result<Event>>,
+    watched: HashSet<PathBuf>,
+    complete: bool,
+}
+
+impl ProjectWatcher {
+    /// Scan `config`'s scan paths and start watching them
+    pub fn new(config: Config) -> Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(sender).ok();
+
+        let mut watcher = Self {
+            index: ProjectIndex::new(config)?,
+            complete: watcher.is_some(),
+            watcher,
+            events,
+            watched: HashSet::new(),
+        };
+        for (root, _) in watcher.index.roots.clone() {
+            watcher.watch(&root);
+        }
+
+        Ok(watcher)
+    }
+
+    pub fn projects(&self) -> &[Project] {
+        self.index.projects()
+    }
+
+    /// Whether every directory that could hold a project is watched
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Watch the directories at or below `dir`, stopping at the watch limit
+    fn watch(&mut self, dir: &Path) {
+        // inotify drops the watches of removed directories itself
+        self.watched
+            .retain(|path| !path.starts_with(dir) || path.exists());
+
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        for path in self.index.watch_dirs(dir) {
+            if !self.complete {
+                return;
+            }
+            if self.watched.contains(&path) {
+                continue;
+            }
+            match watcher.watch(&path, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    self.watched.insert(path);
+                }
+                Err(err) if matches!(err.kind, notify::ErrorKind::MaxFilesWatch) => {
+                    self.complete = false;
+                }
+                // Removed again before we got to it, or unreadable, which the
+                // scan reports
+                Err(_) => {}
+            }
+        }
+    }
+
+    /// Wait up to `timeout` for filesystem events, then apply every event
+    /// that has arrived
+    pub fn poll(&mut self, timeout: Duration) -> Result<Vec<Change>> {
+        if self.watcher.is_none() {
+            std::thread::sleep(timeout);
+            return Ok(Vec::new());
+        }
+        let first = match self.events.recv_timeout(timeout) {
+            Ok(event) => event,
+            Err(mpsc::RecvTimeoutError::Timeout) => return Ok(Vec::new()),
+            Err(mpsc::RecvTimeoutError::Disconnected) => bail!("File watcher stopped"),
+        };
+
+        let mut changed = BTreeSet::new();
+        let mut overflowed = false;
+        for event in std::iter::once(first).chain(self.events.try_iter()) {
+            let event = event.context("File watcher failed")?;
+            if event.need_rescan() {
+                overflowed = true;
+            }
+            if matches!(
+                event.kind,
+                EventKind::Create(_)
+                    | EventKind::Remove(_)
+                    | EventKind::Modify(ModifyKind::Name(_))
+            ) {
+                changed.extend(event.paths);
+            }
+        }
+
+        // The kernel dropped events, so anything could have changed
+        if overflowed {
+            return self.rescan();
+        }
+
+        let mut changes = Vec::new();
+        let mut refreshed: Vec<PathBuf> = Vec::new();
+        // Sorted, so a directory comes before everything below it and covers it
+        for path in changed {
+            if refreshed.iter().any(|dir| path.starts_with(dir)) {
+                continue;
+            }
+            // Watch before looking, so nothing created in between is missed
+            self.watch(&path);
+            changes.extend(self.index.refresh(&path));
+            refreshed.push(path);
+        }
+
+        Ok(changes)
+    }
+
+    /// Scan everything again and watch any directories not yet watched
+    pub fn rescan(&mut self) -> Result<Vec<Change>> {
+        let changes = self.index.rescan()?;
+        for (root, _) in self.index.roots.clone() {
+            self.watch(&root);
+        }
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn config(root: &Path) -> Config {
+        Config {
+            scan_paths: vec![root.into()],
+            project_markers: vec![".git".to_string(), "Cargo.toml".to_string()],
+            max_depth: 2,
+            exclude: vec!["node_modules".to_string()],
+            ..Config::default()
+        }
+    }
+
+    fn paths(projects: &[Project]) -> Vec<String> {
+        let mut paths: Vec<String> = projects.iter().map(|p| p.display_path.clone()).collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_refresh_adds_and_removes_projects() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("api/.git")).unwrap();
+        let mut index = ProjectIndex::new(config(root)).unwrap();
+        assert_eq!(paths(index.projects()), ["api"]);
+
+        // A clone appears
+        fs::create_dir_all(root.join("work/web/.git")).unwrap();
+        let changes = index.refresh(&root.join("work"));
+        assert!(matches!(&changes[..], [Change::Added(p)] if p.display_path == "work/web"));
+
+        // A marker file appears in an existing directory
+        fs::write(root.join("work/Cargo.toml"), "").unwrap();
+        index.refresh(&root.join("work/Cargo.toml"));
+        assert_eq!(paths(index.projects()), ["api", "work", "work/web"]);
+
+        // Too deep, or excluded
+        fs::create_dir_all(root.join("work/web/deep/.git")).unwrap();
+        fs::create_dir_all(root.join("node_modules/dep/.git")).unwrap();
+        assert!(index.refresh(&root.join("work/web/deep")).is_empty());
+        assert!(index.refresh(&root.join("node_modules")).is_empty());
+
+        // Removed
+        fs::remove_dir_all(root.join("work")).unwrap();
+        let changes = index.refresh(&root.join("work"));
+        assert_eq!(changes.len(), 2);
+        assert_eq!(paths(index.projects()), ["api"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_refresh_skips_symlinked_duplicates() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("data/.git")).unwrap();
+        let mut index = ProjectIndex::new(config(root)).unwrap();
+
+        std::os::unix::fs::symlink(root.join("data"), root.join("client")).unwrap();
+        assert!(index.refresh(&root.join("client")).is_empty());
+        index.refresh(root);
+        assert_eq!(paths(index.projects()), ["data"]);
+        assert_eq!(
+            paths(index.projects()),
+            paths(&scan(&config(root)).unwrap().projects)
+        );
+
+        // Two links to a new project appearing at once add it once
+        fs::create_dir_all(root.join("work/web/.git")).unwrap();
+        std::os::unix::fs::symlink(root.join("work/web"), root.join("work/web-link")).unwrap();
+        let changes = index.refresh(&root.join("work"));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(index.projects().len(), 2);
+    }
+
+    #[test]
+    fn test_watch_dirs_skip_markers_and_excludes() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("api/.git/objects")).unwrap();
+        fs::create_dir_all(root.join("api/node_modules/dep")).unwrap();
+        fs::create_dir_all(root.join("a/b/c/d")).unwrap();
+
+        let index = ProjectIndex::new(config(root)).unwrap();
+        let mut dirs: Vec<PathBuf> = index.watch_dirs(root);
+        dirs.sort();
+        assert_eq!(
+            dirs,
+            [
+                root.to_path_buf(),
+                root.join("a"),
+                root.join("a/b"),
+                root.join("api")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_watcher_sees_new_clone() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("api/.git")).unwrap();
+        let mut watcher = ProjectWatcher::new(config(root)).unwrap();
+        assert!(watcher.is_complete());
+
+        fs::create_dir_all(root.join("web/.git")).unwrap();
+        let mut added = Vec::new();
+        for _ in 0..50 {
+            added.extend(watcher.poll(Duration::from_millis(100)).unwrap());
+            if paths(watcher.projects()).len() == 2 {
+                break;
+            }
+        }
+        assert_eq!(paths(watcher.projects()), ["api", "web"]);
+        assert!(matches!(&added[..], [Change::Added(p)] if p.display_path == "web"));
+    }
+}
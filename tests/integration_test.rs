@@ -32,9 +32,10 @@ fn test_end_to_end_project_scanning_and_matching() {
 
     // Create config
     let config = pj::config::Config {
-        scan_paths: vec![temp_dir.path().to_path_buf()],
+        scan_paths: vec![temp_dir.path().into()],
         project_markers: vec![".git".to_string(), ".jj".to_string()],
         max_depth: 3,
+        ..pj::config::Config::default()
     };
 
     // Scan for projects
@@ -74,9 +75,13 @@ fn test_end_to_end_project_scanning_and_matching() {
 fn test_config_integration() {
     // Test that config can be serialized and deserialized
     let config = pj::config::Config {
-        scan_paths: vec![PathBuf::from("/test/path1"), PathBuf::from("/test/path2")],
+        scan_paths: vec![
+            PathBuf::from("/test/path1").into(),
+            PathBuf::from("/test/path2").into(),
+        ],
         project_markers: vec![".git".to_string(), "Cargo.toml".to_string()],
         max_depth: 4,
+        ..pj::config::Config::default()
     };
 
     let toml_str = toml::to_string(&config).unwrap();
@@ -102,9 +107,10 @@ fn test_deep_nested_project_scanning() {
 
     // Test with sufficient max_depth
     let config = pj::config::Config {
-        scan_paths: vec![temp_dir.path().to_path_buf()],
+        scan_paths: vec![temp_dir.path().into()],
         project_markers: vec![".git".to_string()],
         max_depth: 5,
+        ..pj::config::Config::default()
     };
 
     let projects = pj::scanner::scan_projects(&config).unwrap();
@@ -116,9 +122,10 @@ fn test_deep_nested_project_scanning() {
 
     // Test with insufficient max_depth
     let config = pj::config::Config {
-        scan_paths: vec![temp_dir.path().to_path_buf()],
+        scan_paths: vec![temp_dir.path().into()],
         project_markers: vec![".git".to_string()],
         max_depth: 3,
+        ..pj::config::Config::default()
     };
 
     let projects = pj::scanner::scan_projects(&config).unwrap();
@@ -152,7 +159,7 @@ fn test_multiple_marker_types() {
 
     // Config with all marker types
     let config = pj::config::Config {
-        scan_paths: vec![temp_dir.path().to_path_buf()],
+        scan_paths: vec![temp_dir.path().into()],
         project_markers: vec![
             ".git".to_string(),
             ".jj".to_string(),
@@ -160,6 +167,7 @@ fn test_multiple_marker_types() {
             "package.json".to_string(),
         ],
         max_depth: 2,
+        ..pj::config::Config::default()
     };
 
     let projects = pj::scanner::scan_projects(&config).unwrap();
@@ -194,9 +202,10 @@ fn test_matcher_with_many_projects() {
     fs::create_dir(special2.join(".git")).unwrap();
 
     let config = pj::config::Config {
-        scan_paths: vec![temp_dir.path().to_path_buf()],
+        scan_paths: vec![temp_dir.path().into()],
         project_markers: vec![".git".to_string()],
         max_depth: 2,
+        ..pj::config::Config::default()
     };
 
     let projects = pj::scanner::scan_projects(&config).unwrap();
@@ -215,14 +224,104 @@ fn test_matcher_with_many_projects() {
 fn test_nonexistent_scan_path_handling() {
     let config = pj::config::Config {
         scan_paths: vec![
-            PathBuf::from("/this/path/does/not/exist"),
-            PathBuf::from("/another/fake/path"),
+            PathBuf::from("/this/path/does/not/exist").into(),
+            PathBuf::from("/another/fake/path").into(),
         ],
         project_markers: vec![".git".to_string()],
         max_depth: 3,
+        ..pj::config::Config::default()
     };
 
     // Should not panic, just return empty results
     let projects = pj::scanner::scan_projects(&config).unwrap();
     assert_eq!(projects.len(), 0);
 }
+
+#[test]
+fn test_mcp_server_over_stdio() {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let temp_dir = TempDir::new().unwrap();
+    let projects = temp_dir.path().join("projects");
+    fs::create_dir_all(projects.join("billing-api/.git")).unwrap();
+    fs::create_dir_all(projects.join("frontend/.git")).unwrap();
+    let config = temp_dir.path().join("config.toml");
+    fs::write(&config, format!("scan_paths = [{:?}]\n", projects)).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pj"))
+        .arg("--config")
+        .arg(&config)
+        .arg("mcp")
+        .env("XDG_STATE_HOME", temp_dir.path().join("state"))
+        .env("HOME", temp_dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(
+            concat!(
+                r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-06-18","capabilities":{},"clientInfo":{"name":"test","version":"0"}}}"#,
+                "\n",
+                r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+                "\n",
+                r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"search_projects","arguments":{"query":"bill"}}}"#,
+                "\n",
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let responses: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[0]["result"]["serverInfo"]["name"], "pj");
+
+    let found = &responses[1]["result"]["structuredContent"]["result"];
+    assert_eq!(found.as_array().unwrap().len(), 1);
+    assert_eq!(
+        found[0]["path"],
+        projects.join("billing-api").to_string_lossy().as_ref()
+    );
+}
+
+#[test]
+fn test_unknown_config_key_warns_but_config_check_fails() {
+    use std::process::Command;
+
+    let temp_dir = TempDir::new().unwrap();
+    let config = temp_dir.path().join("config.toml");
+    fs::write(&config, "max_depth = 2\nscan_path = [\"/x\"]\n").unwrap();
+    let pj = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_pj"))
+            .arg("--config")
+            .arg(&config)
+            .args(args)
+            .env("HOME", temp_dir.path())
+            .output()
+            .unwrap()
+    };
+
+    let show = pj(&["config", "show"]);
+    assert!(show.status.success());
+    let stderr = String::from_utf8(show.stderr).unwrap();
+    assert!(stderr.contains("warning:"));
+    assert!(stderr.contains(":2:1: unknown config key `scan_path`"));
+
+    let check = pj(&["config", "check"]);
+    assert!(!check.status.success());
+    let stderr = String::from_utf8(check.stderr).unwrap();
+    assert!(stderr.contains("error:"));
+    assert!(stderr.contains(":2:1: unknown config key `scan_path`"));
+}